@@ -0,0 +1,40 @@
+// times building lists of increasing length with cons (from a tail-recursive
+// function), where a time per item which stays level shows the building to be
+// linear; run with `cargo bench --bench cons`
+
+use facsimile::runtime::Environment;
+
+use std::time::{ Duration, Instant };
+
+const SIZES: [usize; 4] = [ 25_000, 50_000, 100_000, 200_000 ];
+const RUNS: u32 = 5;
+
+const SRC: &str = "
+(def build (n acc)
+  (if (eq n 0) acc (build (sub n 1) (cons n acc)))
+)
+";
+
+fn main() {
+	println!("building lists with cons, best of {} runs", RUNS);
+
+	for size in SIZES {
+		let mut best = Duration::MAX;
+
+		for _ in 0..RUNS {
+			let mut env = Environment::default();
+			facsimile::eval(SRC, Some(&mut env)).unwrap();
+
+			let start = Instant::now();
+			let built = facsimile::eval(&format!("(length (build {} nil))", size), Some(&mut env)).unwrap();
+			best = best.min(start.elapsed());
+
+			assert_eq!(built, facsimile::Value::Integer(size as i64));
+		}
+
+		println!(
+			"{:>8} items: {:.1?} ({:.0} ns/item)",
+			size, best, best.as_nanos() as f64 / size as f64,
+		);
+	}
+}
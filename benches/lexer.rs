@@ -0,0 +1,54 @@
+// times lexing a generated ~5mb source, borrowing from it as a string and
+// copying from it as a reader; run with `cargo bench`
+
+use facsimile::lexer::Lexer;
+
+use std::fmt::Write;
+use std::time::{ Duration, Instant };
+
+const SIZE: usize = 5_000_000;
+const RUNS: u32 = 10;
+
+fn main() {
+	let src = generate();
+	println!("lexing {} bytes, best of {} runs", src.len(), RUNS);
+
+	report("borrowed (from_str)", || Lexer::from_str(&src).count());
+	report("copied (from_reader)", || Lexer::from_reader(src.as_bytes()).count());
+	report("copied (from_chars)", || Lexer::from_chars(src.chars()).count());
+}
+
+// a data file of the sort generated by other programs, mixing every kind of
+// token (and strings both with and without escapes)
+fn generate() -> String {
+	let mut src = String::new();
+	let mut i = 0;
+
+	while src.len() < SIZE {
+		writeln!(
+			src,
+			"(entry :id-{} \"name number {}\" {} {}.5e-3 [alpha beta-{} gamma?] {{:note \"line\\n{}\" :flag true}}) ; #{}",
+			i, i, i * 7919 % 1_000_003, i % 1000, i % 97, i, i,
+		).unwrap();
+
+		i += 1;
+	}
+
+	src
+}
+
+fn report(name: &str, lex: impl Fn() -> usize) {
+	let mut best = Duration::MAX;
+	let mut tokens = 0;
+
+	for _ in 0..RUNS {
+		let start = Instant::now();
+		tokens = lex();
+		best = best.min(start.elapsed());
+	}
+
+	println!(
+		"{:>22}: {} tokens in {:.1?} ({:.1} mb/s)",
+		name, tokens, best, SIZE as f64 / best.as_secs_f64() / 1e6,
+	);
+}
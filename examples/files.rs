@@ -0,0 +1,31 @@
+// writes a file and reads it back from a program, which needs the io feature
+// (on by default); run with `cargo run --example files`
+
+use facsimile::{ Value, Symbol, ErrorKind };
+use facsimile::runtime::Environment;
+
+fn main() {
+	let path = std::env::temp_dir().join(format!("facsimile-{}.txt", std::process::id()));
+
+	let mut env = Environment {
+		symbols: maplit::hashmap! {
+			Symbol::new("path".into()).unwrap() => Value::String(path.display().to_string()),
+		},
+		..Default::default()
+	};
+
+	let contents = facsimile::eval(r#"
+		(spit path "first line\nsecond line")
+		(slurp path)
+	"#, Some(&mut env)).unwrap();
+	assert_eq!(contents, Value::String("first line\nsecond line".into()));
+
+	std::fs::remove_file(&path).unwrap();
+
+	// failing to read is an error of the program, rather than a panic
+	let error = facsimile::eval("(slurp path)", Some(&mut env)).unwrap_err();
+	assert_eq!(error.kind, ErrorKind::IoError);
+	assert!(error.message.starts_with("cannot read "));
+
+	println!("{}\n{}", contents, error);
+}
@@ -0,0 +1,51 @@
+// passes a counter of the host into a program as a foreign value, which a
+// provided function adds to; run with `cargo run --example foreign`
+
+// errors are larger than clippy would like, as in the library
+#![allow(clippy::result_large_err)]
+
+use facsimile::{ Value, Symbol, Error, ErrorKind };
+use facsimile::runtime::{ Environment, Function };
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+type Counter = Rc<RefCell<i32>>;
+
+fn increment(args: &[Value]) -> Result<Value, Error> {
+	let counter = match args {
+		[ counter ] => counter.downcast::<Counter>(),
+		_ => None,
+	};
+
+	let counter = counter.ok_or_else(|| Error {
+		kind: ErrorKind::TypeError,
+		location: None,
+		source: None,
+		message: "increment expects a counter".into(),
+	})?;
+
+	*counter.borrow_mut() += 1;
+	Ok(Value::Integer(*counter.borrow() as i64))
+}
+
+fn main() {
+	let counter: Counter = Rc::new(RefCell::new(0));
+
+	let mut env = Environment {
+		symbols: maplit::hashmap! {
+			Symbol::new("counter".into()).unwrap() => Value::foreign(counter.clone()),
+		},
+		..Default::default()
+	};
+	env.functions.insert(Symbol::new("increment".into()).unwrap(), Rc::new(Function::Provided(increment)));
+
+	let src = "(increment counter) (define same counter) (increment same)";
+	let result = facsimile::eval(src, Some(&mut env)).unwrap();
+
+	// the program was given the counter itself, rather than a copy
+	assert_eq!(result, Value::Integer(2));
+	assert_eq!(*counter.borrow(), 2);
+
+	println!("counter incremented to {}", counter.borrow());
+}
@@ -0,0 +1,27 @@
+// converts values to and from json, which needs the serde feature; run with
+// `cargo run --example json --features serde`
+
+use facsimile::Value;
+
+fn main() {
+	let config = facsimile::eval(r#"
+		{:name "facsimile" :version [0 1] :ratio 0.5 :debug false :parent nil}
+	"#, None).unwrap();
+
+	let json = serde_json::to_string(&config).unwrap();
+	assert_eq!(
+		json,
+		r#"{"name":"facsimile","version":[0,1],"ratio":0.5,"debug":false,"parent":null}"#,
+	);
+
+	// the keys of objects are read back as keywords, so the value is the same
+	let read: Value = serde_json::from_str(&json).unwrap();
+	assert_eq!(read, config);
+
+	// functions have no json form
+	let function = facsimile::eval("(fun (x) x)", None).unwrap();
+	let error = serde_json::to_string(&function).unwrap_err();
+	assert_eq!(error.to_string(), "function cannot be serialized");
+
+	println!("{}\n{}", json, read);
+}
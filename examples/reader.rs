@@ -0,0 +1,39 @@
+// registers a reader for dates written #date"2024-01-01", which are read as a
+// vector of their year, month, and day; run with `cargo run --example reader`
+
+// errors are larger than clippy would like, as in the library
+#![allow(clippy::result_large_err)]
+
+use facsimile::{ Value, Error, ErrorKind, LocationArea };
+use facsimile::runtime::Environment;
+
+fn read_date(text: &str, location: LocationArea) -> Result<Value, Error> {
+	let parts = text
+		.split('-')
+		.map(|part| part.parse().map(Value::Integer))
+		.collect::<Result<Vec<_>, _>>();
+
+	match parts {
+		Ok(parts) if parts.len() == 3 => Ok(Value::Vector(parts)),
+		_ => Err(Error {
+			kind: ErrorKind::SyntaxError,
+			location: Some(location),
+			source: None,
+			message: format!("invalid date {:?}", text),
+		}),
+	}
+}
+
+fn main() {
+	let mut env = Environment::default();
+	env.register_reader("date", read_date);
+
+	let date = facsimile::eval("(get #date\"2024-01-31\" 2)", Some(&mut env)).unwrap();
+	assert_eq!(date, Value::Integer(31));
+
+	let error = facsimile::eval("#date\"tomorrow\"", Some(&mut env)).unwrap_err();
+	assert_eq!(error.kind, ErrorKind::SyntaxError);
+
+	let error = facsimile::eval("#time\"noon\"", Some(&mut env)).unwrap_err();
+	println!("{}\n{}", date, error);
+}
@@ -0,0 +1,34 @@
+// recurses far deeper than the stack would allow if each call nested within
+// the last, both directly and between two functions; run with
+// `cargo run --example recursion`
+
+use facsimile::Value;
+
+use std::thread;
+
+const DEPTH: i64 = 100_000;
+
+fn main() {
+	// a small stack shows that calls in tail position take none of their own
+	thread::Builder::new()
+		.stack_size(256 * 1024)
+		.spawn(|| {
+			let count = facsimile::eval(&format!("
+				(def count (n) (if (= n 0) 0 (count (- n 1))))
+				(count {})
+			", DEPTH), None).unwrap();
+			assert_eq!(count, Value::Integer(0));
+
+			let even = facsimile::eval(&format!("
+				(def even? (n) (if (= n 0) true (odd? (- n 1))))
+				(def odd? (n) (if (= n 0) false (even? (- n 1))))
+				(even? {})
+			", DEPTH + 1), None).unwrap();
+			assert_eq!(even, Value::Boolean(false));
+		})
+		.unwrap()
+		.join()
+		.unwrap();
+
+	println!("recursed {} calls deep", DEPTH);
+}
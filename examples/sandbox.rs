@@ -0,0 +1,50 @@
+// runs programs which are not trusted within limits, so that one looping
+// forever or recursing too deeply ends with an error rather than hanging or
+// overflowing the stack; run with `cargo run --example sandbox`
+
+use facsimile::ErrorKind;
+use facsimile::limits::Limits;
+use facsimile::runtime::Environment;
+
+use std::time::Duration;
+
+fn main() {
+	let mut env = Environment {
+		limits: Limits::default()
+			.with_steps(100_000)
+			.with_time(Duration::from_secs(5))
+			.with_depth(100)
+			.without_io(),
+		..Default::default()
+	};
+
+	let looped = facsimile::eval("(loop [n 0] (recur (add n 1)))", Some(&mut env)).unwrap_err();
+	assert_eq!(looped.kind, ErrorKind::ResourceLimit);
+
+	// the limit cannot be caught by the program
+	let caught = facsimile::eval("(try (loop [] (recur)) (catch e e))", Some(&mut env)).unwrap_err();
+	assert_eq!(caught.kind, ErrorKind::ResourceLimit);
+
+	let deep = facsimile::eval("
+		(def depth (n) (add 1 (depth (add n 1))))
+		(depth 0)
+	", Some(&mut env)).unwrap_err();
+	assert_eq!(deep.kind, ErrorKind::ResourceLimit);
+
+	// each evaluation is counted afresh
+	let sum = facsimile::eval("(reduce + (range 100))", Some(&mut env)).unwrap();
+	assert_eq!(sum.to_string(), "4950");
+
+	// nor can files be read, whether directly or as modules
+	let read = facsimile::eval("(slurp \"/etc/passwd\")", Some(&mut env)).unwrap_err();
+	assert_eq!(read.kind, ErrorKind::ResourceLimit);
+
+	let imported = facsimile::eval("(import \"/etc/passwd\")", Some(&mut env)).unwrap_err();
+	assert_eq!(imported.kind, ErrorKind::ResourceLimit);
+
+	// or anything printed
+	let printed = facsimile::eval("(print \"hello\")", Some(&mut env)).unwrap_err();
+	assert_eq!(printed.kind, ErrorKind::ResourceLimit);
+
+	println!("{}\n{}\n{}\n{}", looped, deep, read, imported);
+}
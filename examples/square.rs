@@ -0,0 +1,29 @@
+// registers a function of the host, which a program calls both directly and by
+// passing it to map; run with `cargo run --example square`
+
+// errors are larger than clippy would like, as in the library
+#![allow(clippy::result_large_err)]
+
+use facsimile::{ Value, Error, ErrorKind };
+use facsimile::runtime::Environment;
+
+fn main() {
+	let mut env = Environment::default();
+	env.register_fn("square", |args| match args {
+		[ Value::Integer(integer) ] => Ok(Value::Integer(integer * integer)),
+		_ => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "square expects an integer".into(),
+		}),
+	});
+
+	let direct = facsimile::eval("(square 7)", Some(&mut env)).unwrap();
+	assert_eq!(direct, Value::Integer(49));
+
+	let mapped = facsimile::eval("(map square [1 2 3])", Some(&mut env)).unwrap();
+	assert_eq!(mapped, Value::Vector(vec![ Value::Integer(1), Value::Integer(4), Value::Integer(9) ]));
+
+	println!("{} {}", direct, mapped);
+}
@@ -0,0 +1,664 @@
+use super::{ Value, Symbol, Error, ErrorKind, LocationArea, LocationPoint };
+use super::list::List;
+use super::parser::{ self, Readers };
+
+use std::fmt;
+use std::rc::Rc;
+
+// the width source is fitted to when printed, counting a tab as four columns
+const MAX_WIDTH: usize = 80;
+const TAB_WIDTH: usize = 4;
+
+// the version of the json format trees are written in, increased whenever the
+// format changes incompatibly
+pub const JSON_VERSION: u32 = 1;
+
+// a parsed expression, whose children are shared so that cloning is cheap
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ast {
+	pub kind: AstKind,
+	pub location: LocationArea,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AstKind {
+	// a whole source, whose expressions are evaluated in turn
+	Program(Rc<[Ast]>),
+
+	// (...), [...], and {...} respectively
+	List(Rc<[Ast]>),
+	Vector(Rc<[Ast]>),
+	Map(Rc<[Ast]>),
+
+	Symbol(Symbol),
+	Keyword(Symbol),
+
+	Integer(i64),
+	Number(f64),
+	Char(char),
+	String(String),
+	Boolean(bool),
+	Nil,
+}
+
+impl Ast {
+	// spans from the start of the source to the end of the last expression
+	pub fn program(items: Vec<Ast>) -> Self {
+		let end = items.last().map_or(LocationPoint::default(), |item| item.location.end);
+
+		Self {
+			kind: AstKind::Program(items.into()),
+			location: LocationArea { start: LocationPoint::default(), end },
+		}
+	}
+
+	// the value the runtime evaluates, where a program becomes a block
+	pub fn to_value(&self) -> Result<Value, Error> {
+		self.to_value_with(&Readers::new())
+	}
+
+	// the value of the tree, reading any tagged forms with the given readers (or
+	// those provided)
+	pub fn to_value_with(&self, readers: &Readers) -> Result<Value, Error> {
+		let values = |items: &[Ast]| items
+			.iter()
+			.map(|item| item.to_value_with(readers))
+			.collect::<Result<Vec<_>, _>>();
+
+		Ok(match &self.kind {
+			AstKind::Program(items) => {
+				let mut block = vec![ Value::Symbol(Symbol("block".into())) ];
+				block.extend(values(items)?);
+
+				Value::List(List::from(block).located(self.location))
+			},
+
+			AstKind::List(items) => match &items[..] {
+				[
+					Ast { kind: AstKind::Symbol(head), .. },
+					Ast { kind: AstKind::Symbol(tag), .. },
+					form,
+				] if head.value() == "tagged" => return read_tagged(tag, form, self.location, readers),
+				_ => Value::List(List::from(values(items)?).located(self.location)),
+			},
+			AstKind::Vector(items) => Value::Vector(values(items)?),
+			AstKind::Map(items) => {
+				if items.len() % 2 != 0 {
+					return Err(Error {
+						kind: ErrorKind::SyntaxError,
+						location: items.last().map(|key| key.location),
+						source: None,
+						message: "key has no value in map literal".into(),
+					})
+				}
+
+				let mut items = values(items)?.into_iter();
+				let mut entries = Vec::new();
+				while let (Some(key), Some(value)) = (items.next(), items.next()) {
+					entries.push((key, value));
+				}
+
+				Value::map(entries)
+			},
+
+			AstKind::Symbol(symbol) => Value::Symbol(symbol.clone()),
+			AstKind::Keyword(symbol) => Value::Keyword(symbol.clone()),
+
+			AstKind::Integer(integer) => Value::Integer(*integer),
+			AstKind::Number(number) => Value::Number(*number),
+			AstKind::Char(ch) => Value::Char(*ch),
+			AstKind::String(string) => Value::String(string.clone()),
+			AstKind::Boolean(boolean) => Value::Boolean(*boolean),
+			AstKind::Nil => Value::nil(),
+		})
+	}
+
+	// the tree of a value, as for a form given by a macro, with every node at the
+	// given location; only a value which could be written as source has one
+	pub fn from_value(value: &Value, location: LocationArea) -> Option<Self> {
+		let trees = |items: &mut dyn Iterator<Item = &Value>| items
+			.map(|item| Self::from_value(item, location))
+			.collect::<Option<Rc<[_]>>>();
+
+		let kind = match value {
+			Value::List(list) if list.is_empty() => AstKind::Nil,
+			Value::List(list) => AstKind::List(trees(&mut list.iter())?),
+			Value::Vector(items) => AstKind::Vector(trees(&mut items.iter())?),
+			Value::Map(entries) => AstKind::Map(trees(
+				&mut entries.iter().flat_map(|(key, value)| [ key, value ]),
+			)?),
+
+			Value::Symbol(symbol) => AstKind::Symbol(symbol.clone()),
+			Value::Keyword(symbol) => AstKind::Keyword(symbol.clone()),
+
+			Value::Integer(integer) => AstKind::Integer(*integer),
+			Value::Number(number) => AstKind::Number(*number),
+			Value::Char(ch) => AstKind::Char(*ch),
+			Value::String(string) => AstKind::String(string.clone()),
+			Value::Boolean(boolean) => AstKind::Boolean(*boolean),
+
+			Value::Set(_) | Value::Function(_) | Value::Reduced(_) | Value::Foreign(_) => return None,
+		};
+
+		Some(Self { kind, location })
+	}
+}
+
+// a tagged form is read by the reader for its tag, given the contents of a
+// string or the source of any other form
+fn read_tagged(
+	tag: &Symbol,
+	form: &Ast,
+	location: LocationArea,
+	readers: &Readers,
+) -> Result<Value, Error> {
+	let reader = readers
+		.get(tag)
+		.copied()
+		.or_else(|| parser::provided_reader(tag.value()))
+		.ok_or_else(|| Error {
+			kind: ErrorKind::SyntaxError,
+			location: Some(location),
+			source: None,
+			message: format!("no reader for tag #{}", tag.value()),
+		})?;
+
+	let text = match &form.kind {
+		AstKind::String(string) => string.clone(),
+		_ => form.to_inline(),
+	};
+
+	reader(&text, form.location).map_err(|mut error| {
+		error.location = error.location.or(Some(location));
+		error
+	})
+}
+
+impl Ast {
+	// canonical source for the expression, which parses back to the same tree
+	// (though not necessarily the same locations); a program has its forms
+	// separated by blank lines
+	pub fn to_source(&self) -> String {
+		Printer::new(None, &[]).print(self)
+	}
+
+	// canonical source for the expression, keeping the comments of the source it
+	// was parsed from (given by their locations), and the grouping of its
+	// top-level forms by blank lines
+	pub fn to_source_with_comments(&self, src: &str, comments: &[LocationArea]) -> String {
+		Printer::new(Some(src), comments).print(self)
+	}
+
+	// the source of the expression on a single line
+	pub fn to_inline(&self) -> String {
+		let sequence = |open: &str, items: &[Ast], close: &str| format!(
+			"{}{}{}",
+			open,
+			items.iter().map(Self::to_inline).collect::<Vec<_>>().join(" "),
+			close,
+		);
+
+		match &self.kind {
+			AstKind::Program(items) => sequence("", items, ""),
+			AstKind::List(items) => if let Some(string) = interpolation(self, items) {
+				string
+			} else if let Some((prefix, quoted)) = quoted(items) {
+				format!("{}{}", prefix, quoted.to_inline())
+			} else {
+				sequence("(", items, ")")
+			},
+			AstKind::Vector(items) => sequence("[", items, "]"),
+			AstKind::Map(items) => sequence("{", items, "}"),
+
+			AstKind::Symbol(symbol) => symbol.value().into(),
+			AstKind::Keyword(symbol) => format!(":{}", symbol.value()),
+
+			AstKind::Integer(integer) => integer.to_string(),
+			// the shortest representation which reads back as the same number
+			AstKind::Number(number) if number.is_nan() => "nan".into(),
+			AstKind::Number(number) => format!("{:?}", number),
+			AstKind::Char(ch) => char_source(*ch),
+			AstKind::String(string) => string_source(string),
+			AstKind::Boolean(boolean) => boolean.to_string(),
+			AstKind::Nil => "nil".into(),
+		}
+	}
+}
+
+impl Ast {
+	// the tree as a json document of its nodes, along with the version of the
+	// format (as documented in the readme)
+	pub fn to_json(&self) -> String {
+		let mut json = format!("{{\"version\":{},\"ast\":", JSON_VERSION);
+		self.write_json(&mut json);
+		json.push('}');
+
+		json
+	}
+
+	fn write_json(&self, out: &mut String) {
+		let kind = match &self.kind {
+			AstKind::Program(_) => "program",
+			AstKind::List(_) => "list",
+			AstKind::Vector(_) => "vector",
+			AstKind::Map(_) => "map",
+			AstKind::Symbol(_) => "symbol",
+			AstKind::Keyword(_) => "keyword",
+			AstKind::Integer(_) => "integer",
+			AstKind::Number(_) => "number",
+			AstKind::Char(_) => "char",
+			AstKind::String(_) => "string",
+			AstKind::Boolean(_) => "boolean",
+			AstKind::Nil => "nil",
+		};
+
+		let point = |point: LocationPoint| format!(
+			"{{\"line\":{},\"column\":{},\"byte\":{},\"index\":{}}}",
+			point.line, point.column, point.byte, point.index,
+		);
+
+		out.push_str(&format!(
+			"{{\"kind\":\"{}\",\"location\":{{\"start\":{},\"end\":{}}}",
+			kind, point(self.location.start), point(self.location.end),
+		));
+
+		// numbers are given as strings of their source, as json numbers cannot
+		// hold every integer or float exactly
+		let value = match &self.kind {
+			AstKind::Program(items) |
+			AstKind::List(items) |
+			AstKind::Vector(items) |
+			AstKind::Map(items) => {
+				out.push_str(",\"items\":[");
+				for (i, item) in items.iter().enumerate() {
+					if i > 0 {
+						out.push(',');
+					}
+
+					item.write_json(out);
+				}
+
+				out.push_str("]}");
+				return
+			},
+			AstKind::Symbol(symbol) | AstKind::Keyword(symbol) => json_string(symbol.value()),
+			AstKind::Integer(_) | AstKind::Number(_) => json_string(&self.to_inline()),
+			AstKind::Char(ch) => json_string(&ch.to_string()),
+			AstKind::String(string) => json_string(string),
+			AstKind::Boolean(boolean) => boolean.to_string(),
+			AstKind::Nil => "null".into(),
+		};
+
+		out.push_str(&format!(",\"value\":{}}}", value));
+	}
+}
+
+impl fmt::Display for Ast {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.to_source())
+	}
+}
+
+// lays out a tree, along with any comments from its source
+struct Printer<'a> {
+	out: String,
+	src: Option<&'a str>,
+	// the comments yet to be written, in order
+	comments: std::iter::Peekable<std::slice::Iter<'a, LocationArea>>,
+	// the line in the source of what was last written
+	line: usize,
+}
+
+impl<'a> Printer<'a> {
+	fn new(src: Option<&'a str>, comments: &'a [LocationArea]) -> Self {
+		Self { out: String::new(), src, comments: comments.iter().peekable(), line: 0 }
+	}
+
+	fn print(mut self, ast: &Ast) -> String {
+		self.write(ast, 0);
+
+		if matches!(ast.kind, AstKind::Program(_)) && !self.out.is_empty() {
+			self.out.push('\n');
+		}
+
+		self.out
+	}
+
+	fn write(&mut self, ast: &Ast, indent: usize) {
+		let (open, close, items) = match &ast.kind {
+			AstKind::Program(items) => {
+				for item in items.iter() {
+					self.write_comments(item.location.start.byte, 0, true);
+					if !self.out.is_empty() {
+						self.push_break(item.location.start.line, 0, true);
+					}
+
+					self.write(item, 0);
+				}
+
+				self.write_comments(usize::MAX, 0, true);
+				return
+			},
+			AstKind::List(items) if interpolation(ast, items).is_some() =>
+				return self.write_inline(ast),
+			AstKind::List(items) => {
+				if let Some((prefix, quoted)) = quoted(items) {
+					self.out.push_str(&prefix);
+					return self.write(quoted, indent)
+				}
+
+				("(", ")", items)
+			},
+			AstKind::Vector(items) => ("[", "]", items),
+			AstKind::Map(items) => ("{", "}", items),
+			_ => return self.write_inline(ast),
+		};
+
+		// a sequence is only broken over lines if it does not fit on one, or has
+		// comments within it
+		let inline = ast.to_inline();
+		let commented = self.comments.peek().is_some_and(|comment| {
+			comment.start.byte < ast.location.end.byte
+		});
+
+		if !commented && indent * TAB_WIDTH + inline.chars().count() <= MAX_WIDTH {
+			return self.write_inline(ast)
+		}
+
+		// the head of a list stays on the first line, along with the name and
+		// arguments (or condition) of the special forms taking them
+		let header = match &ast.kind {
+			AstKind::List(items) => match items.first().map(|item| &item.kind) {
+				Some(AstKind::Symbol(symbol)) => match symbol.value() {
+					"def" | "defmacro" => 3,
+					"fun" | "fn" | "if" => 2,
+					_ => 1,
+				},
+				_ => 1,
+			},
+			_ => 0,
+		};
+		let header = header.min(items.len());
+
+		self.out.push_str(open);
+		self.line = ast.location.start.line;
+		self.write_line(&items[..header], indent + 1);
+
+		// each entry of a map is kept to a line
+		let step = if matches!(ast.kind, AstKind::Map(_)) { 2 } else { 1 };
+		for entry in items[header..].chunks(step) {
+			self.write_comments(entry[0].location.start.byte, indent + 1, false);
+			self.push_break(entry[0].location.start.line, indent + 1, false);
+			self.write_line(entry, indent + 1);
+		}
+
+		self.write_comments(ast.location.end.byte, indent + 1, false);
+		self.push_break(ast.location.end.line, indent, false);
+		self.out.push_str(close);
+		self.line = ast.location.end.line;
+	}
+
+	// writes items separated by spaces, unless a comment comes between them
+	fn write_line(&mut self, items: &[Ast], indent: usize) {
+		for (i, item) in items.iter().enumerate() {
+			if self.write_comments(item.location.start.byte, indent, false) {
+				self.push_break(item.location.start.line, indent, false);
+			} else if i > 0 {
+				self.out.push(' ');
+			}
+
+			self.write(item, indent);
+		}
+	}
+
+	fn write_inline(&mut self, ast: &Ast) {
+		self.out.push_str(&ast.to_inline());
+		self.line = ast.location.end.line;
+	}
+
+	// writes the comments before the given position, each following what was
+	// last written if on the same line, or on a line of its own otherwise
+	fn write_comments(&mut self, before: usize, indent: usize, blank_lines: bool) -> bool {
+		let src = match self.src {
+			Some(src) => src,
+			None => return false,
+		};
+
+		let mut written = false;
+		while let Some(comment) = self.comments.next_if(|comment| comment.start.byte < before) {
+			if !self.out.is_empty() {
+				if comment.start.line == self.line {
+					self.out.push(' ');
+				} else {
+					self.push_break(comment.start.line, indent, blank_lines);
+				}
+			}
+
+			self.out.push_str(src[comment.byte_range(src)].trim_end());
+			self.line = comment.end.line;
+			written = true;
+		}
+
+		written
+	}
+
+	// starts a new line, after a blank line where a top-level form begins (or,
+	// given the source, only where there was one before the given line)
+	fn push_break(&mut self, line: usize, indent: usize, blank_lines: bool) {
+		self.out.push('\n');
+		if blank_lines && (self.src.is_none() || line > self.line + 1) {
+			self.out.push('\n');
+		}
+
+		push_indent(&mut self.out, indent);
+	}
+}
+
+// the source of a string the parser read as (cat ...), interpolating its
+// expressions (the whole list being located at the string); the parts must be
+// read back the same, so neither consecutive nor empty strings are literals,
+// and there must be some expression
+fn interpolation(ast: &Ast, items: &[Ast]) -> Option<String> {
+	match items.first() {
+		Some(Ast { kind: AstKind::Symbol(symbol), location })
+			if symbol.value() == "cat" && *location == ast.location => (),
+		_ => return None,
+	}
+
+	let mut source = String::from('"');
+	let mut literal = false;
+	let mut expressions = 0;
+	for item in &items[1..] {
+		match &item.kind {
+			AstKind::String(string) if !literal && !string.is_empty() => {
+				let string = string_source(string);
+				source.push_str(&string[1..string.len() - 1]);
+				literal = true;
+			},
+			_ if !interpolable(item) => return None,
+			_ => {
+				source.push_str(&format!("{{{}}}", item.to_inline()));
+				literal = false;
+				expressions += 1;
+			},
+		}
+	}
+
+	source.push('"');
+	(expressions > 0).then_some(source)
+}
+
+// whether the expression can be written within a string, where the lexer
+// cannot find the end of an expression with these characters in it
+fn interpolable(ast: &Ast) -> bool {
+	match &ast.kind {
+		AstKind::Char('"' | '{' | '}') => false,
+		AstKind::Program(items) |
+		AstKind::List(items) |
+		AstKind::Vector(items) |
+		AstKind::Map(items) => items.iter().all(interpolable),
+		_ => true,
+	}
+}
+
+fn json_string(string: &str) -> String {
+	let mut json = String::from('"');
+	for ch in string.chars() {
+		match ch {
+			'"' => json.push_str("\\\""),
+			'\\' => json.push_str("\\\\"),
+			'\n' => json.push_str("\\n"),
+			'\r' => json.push_str("\\r"),
+			'\t' => json.push_str("\\t"),
+			ch if (ch as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", ch as u32)),
+			ch => json.push(ch),
+		}
+	}
+
+	json.push('"');
+	json
+}
+
+// the prefix of a quote or splat form (as the parser reads 'x as (quote x)),
+// named argument (as it reads name: x as (named-arg name x)), or tagged form (as
+// it reads #tag x as (tagged tag x)) and the expression after
+fn quoted(items: &[Ast]) -> Option<(String, &Ast)> {
+	let (name, quoted) = match items {
+		[ Ast { kind: AstKind::Symbol(symbol), .. }, quoted ] => (symbol.value(), quoted),
+		[
+			Ast { kind: AstKind::Symbol(symbol), .. },
+			Ast { kind: AstKind::Symbol(label), .. },
+			value,
+		] if symbol.value() == "named-arg" => return Some((format!("{}: ", label.value()), value)),
+		[
+			Ast { kind: AstKind::Symbol(symbol), .. },
+			Ast { kind: AstKind::Symbol(tag), .. },
+			value,
+		] if symbol.value() == "tagged" => {
+			// a form not opened by a delimiter must be separated from the tag
+			let separator = match value.kind {
+				AstKind::String(_) | AstKind::List(_) | AstKind::Vector(_) | AstKind::Map(_) => "",
+				_ => " ",
+			};
+
+			return Some((format!("#{}{}", tag.value(), separator), value))
+		},
+		_ => return None,
+	};
+
+	let prefix = match name {
+		"quote" => "'",
+		"quasiquote" => "`",
+		"unquote" => ",",
+		"unquote-splicing" => ",@",
+		"splat" => "..",
+		_ => return None,
+	};
+
+	Some((prefix.into(), quoted))
+}
+
+fn push_indent(out: &mut String, indent: usize) {
+	out.extend(std::iter::repeat_n('\t', indent));
+}
+
+fn char_source(ch: char) -> String {
+	let name = match ch {
+		'\x07' => "alarm",
+		'\x08' => "backspace",
+		'\x7f' => "delete",
+		'\x1b' => "escape",
+		'\n' => "newline",
+		'\0' => "null",
+		'\r' => "return",
+		' ' => "space",
+		'\t' => "tab",
+		// any other which could not be read as written is escaped
+		ch if ch.is_control() || ch.is_whitespace() => return format!("#\\\\u{{{:x}}}", ch as u32),
+		_ => return format!("#\\{}", ch),
+	};
+
+	format!("#\\{}", name)
+}
+
+// quoted with the escapes the lexer reads, and braces doubled so as not to be
+// read as interpolation
+fn string_source(string: &str) -> String {
+	let mut source = String::from('"');
+	for ch in string.chars() {
+		match ch {
+			'"' => source.push_str("\\\""),
+			'\\' => source.push_str("\\\\"),
+			'\n' => source.push_str("\\n"),
+			'\r' => source.push_str("\\r"),
+			'\t' => source.push_str("\\t"),
+			'{' => source.push_str("{{"),
+			'}' => source.push_str("}}"),
+			ch if ch.is_control() => source.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+			ch => source.push(ch),
+		}
+	}
+
+	source.push('"');
+	source
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ Ast, AstKind };
+	use crate::parse;
+
+	// whether two trees have the same shape and atoms, wherever they are; numbers
+	// are compared by their bits, so that -0.0 differs from 0.0
+	fn same(a: &Ast, b: &Ast) -> bool {
+		match (&a.kind, &b.kind) {
+			(AstKind::Program(a), AstKind::Program(b)) |
+			(AstKind::List(a), AstKind::List(b)) |
+			(AstKind::Vector(a), AstKind::Vector(b)) |
+			(AstKind::Map(a), AstKind::Map(b)) =>
+				a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same(a, b)),
+			(AstKind::Number(a), AstKind::Number(b)) => a.to_bits() == b.to_bits(),
+			(a, b) => a == b,
+		}
+	}
+
+	fn round_trip(src: &str) {
+		let ast = parse(src).unwrap();
+		let printed = ast.to_source();
+		let reparsed = parse(&printed).unwrap_or_else(|errors| panic!("{:?} from {:?}", errors, printed));
+
+		assert!(same(&ast, &reparsed), "{:?} printed as {:?}", src, printed);
+	}
+
+	#[test]
+	fn examples() {
+		let mut scripts = 0;
+		for entry in std::fs::read_dir("examples").unwrap() {
+			let path = entry.unwrap().path();
+			if path.extension().is_some_and(|extension| extension == "fax") {
+				round_trip(&std::fs::read_to_string(&path).unwrap());
+				scripts += 1;
+			}
+		}
+
+		assert!(scripts > 0);
+	}
+
+	#[test]
+	fn literals() {
+		let sources = [
+			r"[#\a #\space #\newline #\u{1F600} #\\n #\( #\;]",
+			r#"["tab\tquote\"brace{{}}" "line\
+				continued" "{name} is {(sub year born)}" "{{not}} {x}"]"#,
+			r##"[r"C:\path" r#"say "hi""# "" "\u{7f}\0"]"##,
+			"\"\"\"\n\tindented\n\t  more \"quoted\"\n\t\"\"\"",
+			"[-0.0 0.0 1e300 -1e-300 0.1 1.5 2e0 inf -inf nan 9223372036854775807 -9223372036854775808]",
+			"{:a 1 'b [2 3] `(c ,d ,@e) ..f} #tag (x y: 1)",
+			"(def f (a (b 2)) \"doc\" (match a ([x .. rest] rest) (_ nil)))",
+		];
+
+		for src in sources {
+			round_trip(src);
+		}
+	}
+}
@@ -0,0 +1,291 @@
+use super::{ Value, Symbol, Error, LocationArea };
+use super::ast::{ Ast, AstKind };
+use super::runtime::{ self, Function, Environment };
+
+use std::rc::Rc;
+
+// the forms the runtime handles itself, which are never calls to a macro of the
+// same name
+const SPECIAL_FORMS: &[&str] = &[
+	"quote", "quasiquote", "block", "if", "cond", "match", "let", "loop", "recur",
+	"try", "and", "all", "or", "any", "named-arg", "splat", "define", "set!",
+	"import", "export", "fun", "fn", "def", "defmacro",
+];
+
+// expands every call to a macro within a tree before it is evaluated, expanding
+// each expansion in turn until no calls remain. quoted forms are left as they
+// are, as are calls to a name bound around them (by a let, match, or function,
+// which may bind a function to it instead); calls which cannot be expanded yet,
+// such as to a macro defined by the same expression, are left for the runtime
+// to expand once reached
+pub fn expand(ast: &Ast, env: &mut Environment) -> Result<Ast, Error> {
+	Expander { env, bound: Vec::new() }.expand(ast)
+}
+
+// the form given by expanding a call to a macro (repeatedly, while it gives
+// another), without expanding the forms within it
+pub fn macroexpand(mut form: Value, env: &Environment) -> Result<Value, Error> {
+	loop {
+		let (items, function) = match &form {
+			Value::List(list) => match list.iter().next() {
+				Some(Value::Symbol(name)) => match macro_named(name, env) {
+					Some(function) => (list.iter().skip(1).cloned().collect(), function),
+					None => return Ok(form),
+				},
+				_ => return Ok(form),
+			},
+			_ => return Ok(form),
+		};
+
+		if let Function::Macro { args, body, .. } = &*function {
+			form = runtime::expand_macro(args, body, items, env)?;
+		}
+	}
+}
+
+// the macro a name calls, looked for as the runtime would: first among
+// variables, and then definitions
+fn macro_named(name: &Symbol, env: &Environment) -> Option<Rc<Function>> {
+	if SPECIAL_FORMS.contains(&name.value()) {
+		return None
+	}
+
+	match env.locals.get(name).or_else(|| env.symbols.get(name).cloned()) {
+		Some(Value::Function(function)) => match *function {
+			Function::Macro { .. } => Some(function),
+			_ => None,
+		},
+		_ => match env.functions.get(name) {
+			Some(function) if matches!(**function, Function::Macro { .. }) => Some(function.clone()),
+			_ => None,
+		},
+	}
+}
+
+struct Expander<'a> {
+	env: &'a mut Environment,
+	// the names bound around the form being expanded
+	bound: Vec<Symbol>,
+}
+
+impl Expander<'_> {
+	fn expand(&mut self, ast: &Ast) -> Result<Ast, Error> {
+		let mut ast = ast.clone();
+		while let Some(expansion) = self.expand_call(&ast)? {
+			ast = expansion;
+		}
+
+		let items = match &ast.kind {
+			AstKind::Program(items) |
+			AstKind::List(items) |
+			AstKind::Vector(items) |
+			AstKind::Map(items) => items,
+			_ => return Ok(ast),
+		};
+
+		let head = match (&ast.kind, items.first()) {
+			(AstKind::List(_), Some(Ast { kind: AstKind::Symbol(symbol), .. })) => symbol.value(),
+			_ => "",
+		};
+
+		let bound = self.bound.len();
+		let expanded = match head {
+			"quote" | "quasiquote" | "tagged" => return Ok(ast),
+			// the names of arguments are bound within the body
+			"fun" | "fn" if items.len() >= 2 => {
+				self.bind(&items[1]);
+				self.expand_from(items, 2)
+			},
+			"def" | "defmacro" if items.len() >= 3 => {
+				if let (AstKind::Symbol(name), "defmacro") = (&items[1].kind, head) {
+					self.env.macro_locations.insert(name.clone(), ast.location);
+				}
+
+				self.bind(&items[2]);
+				self.expand_from(items, 3)
+			},
+			// each binding is within those before it
+			"let" | "loop" if items.len() >= 2 => match &items[1].kind {
+				AstKind::Vector(bindings) | AstKind::List(bindings) => {
+					let mut expanded = Vec::new();
+					for pair in bindings.chunks(2) {
+						if let [ _, value ] = pair {
+							let value = self.expand(value)?;
+							self.bind(&pair[0]);
+							expanded.extend([ pair[0].clone(), value ]);
+						} else {
+							expanded.extend(pair.iter().cloned());
+						}
+					}
+
+					let mut items = self.expand_from(items, 2)?;
+					items[1] = with_items(&items[1], expanded);
+					Ok(items)
+				},
+				_ => self.expand_from(items, 0),
+			},
+			// the pattern of each arm is bound within the rest of it
+			"match" => {
+				let mut expanded = self.expand_all(items.iter().take(2))?;
+				for arm in items.iter().skip(2) {
+					expanded.push(match &arm.kind {
+						AstKind::List(arm_items) if !arm_items.is_empty() => {
+							self.bind(&arm_items[0]);
+							let arm_items = self.expand_from(arm_items, 1);
+							self.bound.truncate(bound);
+
+							with_items(arm, arm_items?)
+						},
+						_ => self.expand(arm)?,
+					});
+				}
+
+				Ok(expanded)
+			},
+			// as is the symbol of the catch clause ending a try within its handler
+			"try" => match items.last().map(|clause| (clause, &clause.kind)) {
+				Some((clause, AstKind::List(clause_items))) if matches!(
+					&clause_items[..],
+					[ Ast { kind: AstKind::Symbol(catch), .. }, Ast { kind: AstKind::Symbol(_), .. }, .. ]
+						if catch.value() == "catch",
+				) => {
+					let mut expanded = self.expand_all(items[..items.len() - 1].iter())?;
+					self.bind(&clause_items[1]);
+					expanded.push(with_items(clause, self.expand_from(clause_items, 2)?));
+
+					Ok(expanded)
+				},
+				_ => self.expand_from(items, 0),
+			},
+			_ => self.expand_from(items, 0),
+		};
+		self.bound.truncate(bound);
+
+		Ok(with_items(&ast, expanded?))
+	}
+
+	// the expansion of a call to a macro, if the form is one which can be expanded
+	fn expand_call(&mut self, ast: &Ast) -> Result<Option<Ast>, Error> {
+		let (name, forms) = match &ast.kind {
+			AstKind::List(items) => match items.first() {
+				Some(Ast { kind: AstKind::Symbol(name), .. }) if !self.bound.contains(name) =>
+					(name, &items[1..]),
+				_ => return Ok(None),
+			},
+			_ => return Ok(None),
+		};
+
+		let function = match macro_named(name, self.env) {
+			Some(function) => function,
+			None => return Ok(None),
+		};
+		let (args, body) = match &*function {
+			Function::Macro { args, body, .. } => (args, body),
+			_ => unreachable!(),
+		};
+
+		let forms = forms
+			.iter()
+			.map(|form| form.to_value_with(&self.env.readers))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let expansion = runtime::expand_macro(args, body, forms, self.env)
+			.map_err(|error| expansion_error(error, name, ast.location, self.env))?;
+
+		// a form which cannot be written as source (such as one holding a function)
+		// is left for the runtime to expand
+		Ok(Ast::from_value(&expansion, ast.location))
+	}
+
+	fn expand_all<'a>(&mut self, items: impl Iterator<Item = &'a Ast>) -> Result<Vec<Ast>, Error> {
+		items.map(|item| self.expand(item)).collect()
+	}
+
+	// the items of a node, with those from an index on expanded
+	fn expand_from(&mut self, items: &[Ast], start: usize) -> Result<Vec<Ast>, Error> {
+		let mut expanded = items[..start.min(items.len())].to_vec();
+		expanded.extend(self.expand_all(items.iter().skip(start))?);
+
+		Ok(expanded)
+	}
+
+	// binds every symbol within a pattern, which is more than it may bind, so
+	// that no call to a name it binds is expanded
+	fn bind(&mut self, pattern: &Ast) {
+		match &pattern.kind {
+			AstKind::Symbol(symbol) => self.bound.push(symbol.clone()),
+			AstKind::List(items) | AstKind::Vector(items) | AstKind::Map(items) => {
+				for item in items.iter() {
+					self.bind(item);
+				}
+			},
+			_ => (),
+		}
+	}
+}
+
+// a node of the same kind as another, with the given items
+fn with_items(ast: &Ast, items: Vec<Ast>) -> Ast {
+	let kind = match ast.kind {
+		AstKind::Program(_) => AstKind::Program(items.into()),
+		AstKind::List(_) => AstKind::List(items.into()),
+		AstKind::Vector(_) => AstKind::Vector(items.into()),
+		AstKind::Map(_) => AstKind::Map(items.into()),
+		_ => return ast.clone(),
+	};
+
+	Ast { kind, location: ast.location }
+}
+
+// an error in expanding a macro is reported at the call, naming the macro along
+// with where it was defined (if known)
+fn expansion_error(error: Error, name: &Symbol, location: LocationArea, env: &Environment) -> Error {
+	let defined = match env.macro_locations.get(name) {
+		Some(defined) => format!(" (defined at {}:{})", defined.start.line, defined.start.column),
+		None => String::new(),
+	};
+
+	Error {
+		location: error.location.or(Some(location)),
+		message: format!("in expansion of macro {}{}: {}", name.value(), defined, error.message),
+		..error
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{ eval, ErrorKind };
+
+	fn run(src: &str) -> String {
+		eval(src, None).unwrap().to_string()
+	}
+
+	const UNLESS: &str = "(defmacro unless (c body) `(if ,c nil ,body))";
+
+	#[test]
+	fn unless() {
+		assert_eq!(run(&format!("{} (macroexpand '(unless x y))", UNLESS)), "(if x nil y)");
+
+		// the body is only evaluated when the condition is false
+		assert_eq!(run(&format!("{} [(unless false 1) (unless true (car 5))]", UNLESS)), "[1 nil]");
+	}
+
+	#[test]
+	fn rest_arguments() {
+		let when = "(defmacro when (c ..body) `(if ,c (block ,@body) nil))";
+		assert_eq!(run(&format!("{} (macroexpand '(when x 1 2))", when)), "(if x (block 1 2) nil)");
+		assert_eq!(run(&format!("{} [(when true 1 2) (when false 1)]", when)), "[2 nil]");
+		assert_eq!(run("(defmacro m (a .. rest) `',rest) (m 1 2 3)"), "(2 3)");
+
+		// the forms before the rest are still required
+		let error = eval(&format!("{} (when)", when), None).unwrap_err();
+		assert_eq!(error.message, "in expansion of macro when (defined at 1:1): 0 arguments provided (at least 1 expected)");
+	}
+
+	#[test]
+	fn no_defaults() {
+		let error = eval("(defmacro w (c (d 5)) d)", None).unwrap_err();
+		assert_eq!(error.kind, ErrorKind::ArgumentError);
+		assert_eq!(error.location.map(|location| location.start.column), Some(13));
+	}
+}
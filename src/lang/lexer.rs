@@ -1,36 +1,189 @@
-use super::{ Symbol, Error, ErrorKind, LocationArea, LocationPoint };
+use super::{ Error, ErrorKind, LocationArea, LocationPoint };
+use super::{ is_symbol_start, is_symbol_continue };
 
-pub struct Lexer<'a, I: Iterator<Item = char>> {
-	src: std::iter::Peekable<&'a mut I>,
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fmt;
+use std::io::{ BufReader, Bytes, Read };
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::Chars;
+
+pub struct Lexer<'src, I: Iterator<Item = char>> {
+	src: Peekable<I>,
+	// the whole source when lexing a string, which tokens borrow from
+	text: Option<&'src str>,
+	// otherwise, the characters eaten since the start of the current token,
+	// beginning at the given byte
+	buffer: String,
+	buffer_start: usize,
 	location: LocationPoint,
 	current: LocationPoint,
+	options: LexerOptions,
+	failed: bool,
+	finished: bool,
+	// the name given to errors, such as a file name
+	source: Option<Rc<str>>,
+	// set by the source when it fails, as when a reader has invalid utf-8
+	src_error: Rc<Cell<Option<String>>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LexerOptions {
+	// yield comments as tokens rather than discarding them
+	pub comments: bool,
+	// continue past errors rather than leaving the input in an unknown state
+	pub lenient: bool,
+	// end the tokens with an Eof token just past the end of the input
+	pub eof: bool,
+	// recognise // and /* */ comments
+	pub c_comments: bool,
+	// recognise ; and #| |# comments
+	pub lisp_comments: bool,
+	// the characters which separate tokens
+	pub whitespace: fn(char) -> bool,
+}
+
+impl Default for LexerOptions {
+	fn default() -> Self {
+		Self {
+			comments: false,
+			lenient: false,
+			eof: false,
+			c_comments: true,
+			lisp_comments: true,
+			whitespace: char::is_whitespace,
+		}
+	}
 }
 
-impl<'a, I: Iterator<Item = char>> Lexer<'a, I> {
+impl<'a, I: Iterator<Item = char>> Lexer<'static, &'a mut I> {
 	pub fn new(src: &'a mut I) -> Self {
+		Self::from_chars(src)
+	}
+}
+
+impl<'s> Lexer<'s, Chars<'s>> {
+	// symbols, strings without escapes, and comments borrow from the source
+	#[allow(clippy::should_implement_trait)]
+	pub fn from_str(src: &'s str) -> Self {
+		Self {
+			text: Some(src),
+			..Self::from_chars(src.chars())
+		}
+	}
+}
+
+impl<R: Read> Lexer<'static, ReadChars<R>> {
+	// decodes the reader incrementally, rather than reading it all up front
+	pub fn from_reader(src: R) -> Self {
+		let error = Rc::<Cell<_>>::default();
+
+		let mut lexer = Self::from_chars(ReadChars {
+			bytes: BufReader::new(src).bytes(),
+			buffer: [0; 4],
+			bailed: false,
+			error: Rc::clone(&error),
+		});
+
+		lexer.src_error = error;
+		lexer
+	}
+}
+
+impl<'src, I: Iterator<Item = char>> Lexer<'src, I> {
+	pub fn from_chars(src: I) -> Self {
 		Self {
 			src: src.peekable(),
+			text: None,
+			buffer: String::new(),
+			buffer_start: 0,
 			location: LocationPoint::default(),
 			current: LocationPoint::default(),
+			options: LexerOptions::default(),
+			failed: false,
+			finished: false,
+			source: None,
+			src_error: Rc::default(),
 		}
 	}
 
+	pub fn with_options(mut self, options: LexerOptions) -> Self {
+		self.options = options;
+		self
+	}
+
+	pub fn with_comments(mut self) -> Self {
+		self.options.comments = true;
+		self
+	}
+
+	pub fn lenient(mut self) -> Self {
+		self.options.lenient = true;
+		self
+	}
+
+	pub fn with_eof(mut self) -> Self {
+		self.options.eof = true;
+		self
+	}
+
+	pub fn with_source(mut self, source: impl Into<Rc<str>>) -> Self {
+		self.source = Some(source.into());
+		self
+	}
+
 	fn eat(&mut self) -> Option<char> {
 		let ch = self.src.next()?;
 
 		self.current = self.location;
 		self.location.index += 1;
+		self.location.byte += ch.len_utf8();
 
-		if ch == '\n' {
-			self.location.line += 1;
-			self.location.column = 0;
-		} else {
-			self.location.column += 1;
+		// \r\n and a lone \r each count as a single line break, and a leading
+		// byte order mark takes up no column
+		match ch {
+			'\r' if self.src.peek() == Some(&'\n') => (),
+			'\u{FEFF}' if self.current.index == 0 => (),
+			'\n' | '\r' => {
+				self.location.line += 1;
+				self.location.column = 1;
+			},
+			_ => self.location.column += 1,
+		}
+
+		if self.text.is_none() {
+			self.buffer.push(ch);
 		}
 
 		Some(ch)
 	}
 
+	// the source between two bytes within the current token
+	fn slice(&self, from: usize, to: usize) -> Cow<'src, str> {
+		match self.text {
+			Some(text) => Cow::Borrowed(&text[from..to]),
+			None => Cow::Owned(
+				self.buffer[from - self.buffer_start..to - self.buffer_start].to_owned()
+			),
+		}
+	}
+
+	// the area of the next character, which has been peeked but not eaten
+	fn peeked(&self) -> LocationArea {
+		self.location.into()
+	}
+
+	// an underscore which was just eaten without a digit on both sides
+	fn misplaced_separator(&self) -> Error {
+		Error {
+			kind: ErrorKind::SyntaxError,
+			location: Some(self.current.into()),
+			source: None,
+			message: "misplaced digit separator in number literal".into(),
+		}
+	}
+
 	fn eat_n(&mut self, n: usize) -> Option<String> {
 		let mut string = String::new();
 
@@ -40,247 +193,796 @@ impl<'a, I: Iterator<Item = char>> Lexer<'a, I> {
 
 		Some(string)
 	}
-}
 
-impl<'a, I: Iterator<Item = char>> Iterator for Lexer<'a, I> {
-	type Item = Result<Token, Error>;
+	// eats the rest of a symbol, given the byte its first character began at
+	// a / joins names, qualifying the one after with the one before, as for the
+	// definitions of a module (geometry/area)
+	fn eat_symbol(&mut self, from: usize) -> Cow<'src, str> {
+		while self.src.peek().is_some_and(|ch| is_symbol_continue(*ch) || *ch == '/') {
+			self.eat();
+		}
 
-	fn next(&mut self) -> Option<Self::Item> {
-		while let Some(ch) = self.src.peek() {
-			if ch.is_ascii_whitespace() {
-				self.eat();
-			} else {
-				break
-			}
+		self.slice(from, self.location.byte)
+	}
+
+	fn eat_line_comment(&mut self) -> Cow<'src, str> {
+		let from = self.location.byte;
+		while self.src.peek().is_some_and(|ch| !matches!(ch, '\n' | '\r')) {
+			self.eat();
 		}
 
-		let ch = self.eat()?;
-		let start = self.current;
+		self.slice(from, self.location.byte)
+	}
+
+	// eats the rest of a block comment, given the first character of its opening
+	// delimiter (/* or #|) has been eaten and the second peeked; the closing
+	// delimiter is the opening reversed, and nested comments must be closed too
+	fn eat_block_comment(
+		&mut self,
+		start: LocationPoint,
+		open: char,
+	) -> Result<Cow<'src, str>, Error> {
+		let inner = self.eat().unwrap();
 
-		let kind = match ch {
-			'(' => TokenKind::LeftParen,
-			')' => TokenKind::RightParen,
-			'[' => TokenKind::LeftBracket,
-			']' => TokenKind::RightBracket,
-			'{' => TokenKind::LeftBrace,
-			'}' => TokenKind::RightBrace,
-			'.' => TokenKind::Dot,
+		// unterminated comments are reported at the outermost opening
+		// delimiter, as that is the one left unclosed
+		let opening = LocationArea { start, end: self.current };
 
-			'A'..='Z' | 'a'..='z' | '_' => {
-				let mut symbol = String::from(ch);
-				while let Some('0'..='9' | 'A'..='Z' | 'a'..='z' | '_') = self.src.peek() {
-					symbol.push(self.eat().unwrap());
-				}
+		let from = self.location.byte;
+		let mut depth = 1;
+		let to = loop {
+			match self.eat() {
+				Some(ch) if ch == inner && self.src.peek() == Some(&open) => {
+					let to = self.current.byte;
+					self.eat();
+
+					depth -= 1;
+					if depth == 0 {
+						break to
+					}
+				},
+				Some(ch) if ch == open && self.src.peek() == Some(&inner) => {
+					self.eat();
+					depth += 1;
+				},
+				Some(_) => (),
+				None => return Err(Error {
+					kind: ErrorKind::IncompleteError,
+					location: Some(opening),
+					source: None,
+					message: "unterminated comment".into(),
+				}),
+			}
+		};
+
+		Ok(self.slice(from, to))
+	}
+
+	// lexes the expression between an opening brace (already eaten) and its
+	// closing brace within a string
+	fn eat_interpolation(&mut self) -> Result<Vec<Token<'src>>, Error> {
+		let brace = self.current;
+		let origin = self.location;
 
-				match symbol.as_str() {
-					"true" => TokenKind::Boolean(true),
-					"false" => TokenKind::Boolean(false),
-					"nil" => TokenKind::Nil,
+		let mut depth = 0;
+		let mut quoted = false;
+		loop {
+			let ch = match self.eat() {
+				Some(ch) => ch,
+				None => return Err(Error {
+					kind: ErrorKind::IncompleteError,
+					location: Some(brace.into()),
+					source: None,
+					message: "unterminated interpolation in string".into(),
+				}),
+			};
 
-					_ => TokenKind::Symbol(Symbol::new(symbol).unwrap()),
+			if quoted {
+				if ch == '\\' {
+					self.eat();
+				} else if ch == '"' {
+					quoted = false;
 				}
+			} else if ch == '"' {
+				quoted = true;
+			} else if ch == '{' {
+				depth += 1;
+			} else if ch == '}' {
+				if depth == 0 {
+					break
+				}
+
+				depth -= 1;
+			}
+		}
+
+		let options = LexerOptions {
+			comments: false,
+			lenient: false,
+			eof: false,
+			..self.options
+		};
+
+		// the expression is lexed from the source as it stands, so that tokens
+		// borrow from it and are located within it
+		let (from, to) = (origin.byte, self.current.byte);
+		match self.text {
+			Some(text) => {
+				let mut lexer = Lexer {
+					text: Some(text),
+					..Lexer::from_chars(text[from..to].chars())
+				}.with_options(options);
+				lexer.location = origin;
+
+				lexer.collect()
+			},
+			None => {
+				let source = self.slice(from, to);
+				let mut lexer = Lexer::from_chars(source.chars()).with_options(options);
+				lexer.location = origin;
+
+				lexer.map(|token| token.map(Token::into_owned)).collect()
 			},
+		}
+	}
 
-			'-' | '+' | '0'..='9' => {
-				let mut number = String::from(ch);
-				while let Some('0'..='9' | '_' | '.' | 'E' | 'e') = self.src.peek() {
-					number.push(self.eat().unwrap());
+	// decodes an escape sequence following a backslash (already eaten)
+	fn eat_escape(&mut self) -> Result<char, Error> {
+		let before = self.current;
+		let ech = match self.eat() {
+			Some(ech) => ech,
+			None => return Err(Error {
+				kind: ErrorKind::IncompleteError,
+				location: Some(self.current.into()),
+				source: None,
+				message: "unexpected end whilst parsing escape".into(),
+			}),
+		};
+
+		Ok(match ech {
+			'x' | 'u' | 'U' => self.eat_hex_escape(ech, before)?,
+
+			'n' => '\n',
+			'r' => '\r',
+			't' => '\t',
+			'a' => '\x07',
+			'b' => '\x08',
+			'e' => '\x1b',
+			'f' => '\x0c',
+			'v' => '\x0b',
+
+			'0' => '\0',
+			'\\' => '\\',
+			'"' => '"',
+			'\'' => '\'',
+
+			_ => return Err(Error {
+				kind: ErrorKind::SyntaxError,
+				location: Some(LocationArea { start: before, end: self.current }),
+				source: None,
+				message: format!("{:?} is not a valid escape", ech),
+			}),
+		})
+	}
+
+	// decodes the hex digits following \x, \u, or \U (or a braced \u{...})
+	fn eat_hex_escape(&mut self, kind: char, before: LocationPoint) -> Result<char, Error> {
+		let hex = if kind == 'u' && self.src.peek() == Some(&'{') {
+			self.eat();
+
+			let mut hex = String::new();
+			loop {
+				match self.eat() {
+					Some('}') => break hex,
+					Some(ch) => hex.push(ch),
+					None => return Err(Error {
+						kind: ErrorKind::IncompleteError,
+						location: Some(LocationArea { start: before, end: self.current }),
+						source: None,
+						message: "unexpected end whilst parsing escape".into(),
+					}),
+				}
+			}
+		} else {
+			let digits = match kind {
+				'x' => 2,
+				'u' => 4,
+				_ => 8,
+			};
+
+			match self.eat_n(digits) {
+				Some(hex) => hex,
+				None => return Err(Error {
+					kind: ErrorKind::IncompleteError,
+					location: Some(LocationArea { start: before, end: self.current }),
+					source: None,
+					message: "unexpected end whilst parsing escape".into(),
+				}),
+			}
+		};
+
+		let code = match u32::from_str_radix(&hex, 16) {
+			// from_str_radix would also accept a sign
+			Ok(code) if hex.chars().all(|ch| ch.is_ascii_hexdigit()) => code,
+			_ => return Err(Error {
+				kind: ErrorKind::SyntaxError,
+				location: Some(LocationArea { start: before, end: self.current }),
+				source: None,
+				message: format!("{:?} is invalid hex", hex),
+			}),
+		};
+
+		char::from_u32(code).ok_or_else(|| Error {
+			kind: ErrorKind::SyntaxError,
+			location: Some(LocationArea { start: before, end: self.current }),
+			source: None,
+			message: format!("{} is not a valid character", code),
+		})
+	}
+}
+
+impl<'src, I: Iterator<Item = char>> Lexer<'src, I> {
+	fn lex(&mut self) -> Option<Result<Token<'src>, Error>> {
+		let (start, kind) = loop {
+			while let Some(ch) = self.src.peek() {
+				if (self.options.whitespace)(*ch) {
+					self.eat();
+				} else {
+					break
 				}
+			}
+
+			self.buffer.clear();
+			self.buffer_start = self.location.byte;
+
+			let ch = self.eat()?;
+			let start = self.current;
 
-				use std::str::FromStr;
-				TokenKind::Number(match f32::from_str(&number) {
-					Ok(number) => number,
-					Err(_) => return Some(Err(Error {
+			let kind = match ch {
+				'(' => TokenKind::LeftParen,
+				')' => TokenKind::RightParen,
+				'[' => TokenKind::LeftBracket,
+				']' => TokenKind::RightBracket,
+				'{' => TokenKind::LeftBrace,
+				'}' => TokenKind::RightBrace,
+				// .. marks the rest of a pattern, rather than a path, and ..x spreads
+				// the items of x as arguments
+				'.' if self.src.peek() == Some(&'.') => {
+					self.eat();
+					match self.src.peek() {
+						Some(&ch) if !(self.options.whitespace)(ch) && !matches!(ch, ')' | ']' | '}') =>
+							TokenKind::Splat,
+						_ => TokenKind::Symbol("..".into()),
+					}
+				},
+				'.' => TokenKind::Dot,
+
+				'\'' => TokenKind::Quote,
+				'`' => TokenKind::Quasiquote,
+				',' => if self.src.peek() == Some(&'@') {
+					self.eat();
+					TokenKind::UnquoteSplice
+				} else {
+					TokenKind::Unquote
+				},
+
+				'r' if matches!(self.src.peek(), Some('"' | '#')) => {
+					let mut hashes = 0;
+					while self.src.peek() == Some(&'#') {
+						self.eat();
+						hashes += 1;
+					}
+
+					if self.eat() != Some('"') {
+						return Some(Err(Error {
+							kind: ErrorKind::SyntaxError,
+							location: Some(self.current.into()),
+							source: None,
+							message: "expected quote to begin raw string".into(),
+						}))
+					}
+
+					// the string ends at a quote followed by as many hashes as
+					// preceded the opening quote
+					let from = self.location.byte;
+					let to = loop {
+						match self.eat() {
+							Some('"') => {
+								let to = self.current.byte;
+
+								let mut closing = 0;
+								while closing < hashes && self.src.peek() == Some(&'#') {
+									self.eat();
+									closing += 1;
+								}
+
+								if closing == hashes {
+									break to
+								}
+							},
+							Some(_) => (),
+							None => return Some(Err(Error {
+								kind: ErrorKind::IncompleteError,
+								location: Some(LocationArea { start, end: self.current }),
+								source: None,
+								message: "unterminated string".into(),
+							})),
+						}
+					};
+
+					TokenKind::String(self.slice(from, to))
+				},
+				ch if is_symbol_start(ch) => {
+					let symbol = self.eat_symbol(start.byte);
+
+					if symbol.split('/').skip(1).any(|name| !name.starts_with(is_symbol_start)) {
+						return Some(Err(Error {
+							kind: ErrorKind::SyntaxError,
+							location: Some(LocationArea { start, end: self.current }),
+							source: None,
+							message: format!("expected name after '/' in {:?}", symbol),
+						}))
+					}
+
+					match &*symbol {
+						"true" => TokenKind::Boolean(true),
+						"false" => TokenKind::Boolean(false),
+						"nil" => TokenKind::Nil,
+						"inf" => TokenKind::Number(f64::INFINITY),
+						"nan" => TokenKind::Number(f64::NAN),
+
+						// name: names the argument after it
+						_ if self.src.peek() == Some(&':') => {
+							self.eat();
+							TokenKind::Label(symbol)
+						},
+						_ => TokenKind::Symbol(symbol),
+					}
+				},
+				':' => match self.src.peek() {
+					Some(&ch) if is_symbol_start(ch) => {
+						let from = self.location.byte;
+						self.eat();
+						TokenKind::Keyword(self.eat_symbol(from))
+					},
+					_ => return Some(Err(Error {
 						kind: ErrorKind::SyntaxError,
-						location: Some(LocationArea { start, end: self.current }),
-						message: "invalid number literal".into(),
+						location: Some(self.peeked()),
+						source: None,
+						message: "expected identifier after ':'".into(),
 					})),
-				})
-			},
-			'"' | '\'' => {
-				let mut string = String::new();
-				loop {
-					if let Some(nch) = self.eat() {
-						if nch == ch {
-							break
-						} else if nch == '\\' {
-							let before = self.current;
-							if let Some(ech) = self.eat() {
-								string.push(match ech {
-									'x' => match self.eat_n(2) {
-										Some(hex) => match u8::from_str_radix(&hex, 16) {
-											Ok(octet) => octet as char,
-											Err(_) => return Some(Err(Error {
-												kind: ErrorKind::SyntaxError,
-												location: Some(LocationArea {
-													start: before,
-													end: self.current,
-												}),
-												message: format!("{:?} is invalid hex", hex),
-											})),
-										},
-										None => return Some(Err(Error {
-											kind: ErrorKind::SyntaxError,
-											location: Some(self.current.into()),
-											message: "unexpected end whilst parsing escape".into(),
-										})),
-									},
-									'u' => match self.eat_n(4) {
-										Some(hex) => match u16::from_str_radix(&hex, 16) {
-											Ok(word) => match char::from_u32(word as u32) {
-												Some(uni) => uni,
-												None => return Some(Err(Error {
-													kind: ErrorKind::SyntaxError,
-													location: Some(LocationArea {
-														start: before,
-														end: self.current,
-													}),
-													message: format!("{} is not a valid character", word),
-												})),
-											},
-											Err(_) => return Some(Err(Error {
-												kind: ErrorKind::SyntaxError,
-												location: Some(LocationArea {
-													start: before,
-													end: self.current,
-												}),
-												message: format!("{:?} is invalid hex", hex),
-											})),
-										},
-										None => return Some(Err(Error {
-											kind: ErrorKind::SyntaxError,
-											location: Some(self.current.into()),
-											message: "unexpected end whilst parsing escape".into(),
-										})),
-									},
-									'U' =>  match self.eat_n(8) {
-										Some(hex) => match u32::from_str_radix(&hex, 16) {
-											Ok(dword) => match char::from_u32(dword) {
-												Some(uni) => uni,
-												None => return Some(Err(Error {
-													kind: ErrorKind::SyntaxError,
-													location: Some(LocationArea {
-														start: before,
-														end: self.current,
-													}),
-													message: format!("{} is not a valid character", dword),
-												})),
-											},
-											Err(_) => return Some(Err(Error {
-												kind: ErrorKind::SyntaxError,
-												location: Some(LocationArea {
-													start: before,
-													end: self.current,
-												}),
-												message: format!("{:?} is invalid hex", hex),
-											})),
-										},
-										None => return Some(Err(Error {
-											kind: ErrorKind::SyntaxError,
-											location: Some(self.current.into()),
-											message: "unexpected end whilst parsing escape".into(),
-										})),
-									},
-
-									'n' => '\n',
-									'r' => '\r',
-									't' => '\t',
-
-									'0' => '\0',
-									'\\' => '\\',
-
-									_ => return Some(Err(Error {
-										kind: ErrorKind::SyntaxError,
-										location: Some(LocationArea {
-											start: before,
-											end: self.current,
-										}),
-										message: format!("{:?} is not a valid escape", ech),
-									})),
-								});
-							} else {
+				},
+
+				// a sign only begins a number when directly followed by one, and
+				// otherwise begins a symbol (such as - or -main)
+				'-' | '+' if !matches!(self.src.peek(), Some('0'..='9' | '.')) => {
+					let symbol = self.eat_symbol(start.byte);
+
+					match &*symbol {
+						"-inf" => TokenKind::Number(f64::NEG_INFINITY),
+						"+inf" => TokenKind::Number(f64::INFINITY),
+
+						_ => TokenKind::Symbol(symbol),
+					}
+				},
+				'-' | '+' | '0'..='9' => {
+					let mut prev = ch;
+					if !ch.is_ascii_digit() && self.src.peek() == Some(&'0') {
+						prev = self.eat().unwrap();
+					}
+
+					let radix = if prev == '0' {
+						match self.src.peek() {
+							Some('x') => Some((16, "hexadecimal")),
+							Some('o') => Some((8, "octal")),
+							Some('b') => Some((2, "binary")),
+							_ => None,
+						}
+					} else {
+						None
+					};
+
+					if let Some((radix, radix_name)) = radix {
+						self.eat();
+
+						let mut digits = String::new();
+						if ch == '-' {
+							digits.push('-');
+						}
+
+						while let Some('0'..='9' | 'A'..='Z' | 'a'..='z' | '_') = self.src.peek() {
+							let digit = self.eat().unwrap();
+							if digit == '_' {
+								let after_digit = digits.ends_with(|ch: char| ch.is_ascii_alphanumeric());
+								let before_digit = matches!(self.src.peek(), Some(ch) if ch.is_ascii_alphanumeric());
+								if !(after_digit && before_digit) {
+									return Some(Err(self.misplaced_separator()))
+								}
+
+								continue
+							}
+
+							if !digit.is_digit(radix) {
 								return Some(Err(Error {
 									kind: ErrorKind::SyntaxError,
 									location: Some(self.current.into()),
-									message: "unexpected end whilst parsing escape".into(),
+									source: None,
+									message: format!("invalid digit {:?} in {} literal", digit, radix_name),
 								}))
 							}
-						} else {
-							string.push(nch);
+
+							digits.push(digit);
+						}
+
+						if digits.trim_start_matches('-').is_empty() {
+							return Some(Err(Error {
+								kind: ErrorKind::SyntaxError,
+								location: Some(LocationArea { start, end: self.current }),
+								source: None,
+								message: format!("{} literal has no digits", radix_name),
+							}))
 						}
+
+						TokenKind::Integer(match i64::from_str_radix(&digits, radix) {
+							Ok(integer) => integer,
+							Err(_) => return Some(Err(Error {
+								kind: ErrorKind::SyntaxError,
+								location: Some(LocationArea { start, end: self.current }),
+								source: None,
+								message: "invalid number literal".into(),
+							})),
+						})
 					} else {
-						return Some(Err(Error {
-							kind: ErrorKind::SyntaxError,
-							location: Some(self.current.into()),
-							message: "unterminated string".into(),
-						}))
-					}
-				}
+						let mut separated = false;
+						while let Some('0'..='9' | '_' | '.' | 'E' | 'e') = self.src.peek() {
+							let nch = self.eat().unwrap();
+							if nch == '_' {
+								let before_digit = matches!(self.src.peek(), Some('0'..='9'));
+								if !(prev.is_ascii_digit() && before_digit) {
+									return Some(Err(self.misplaced_separator()))
+								}
 
-				TokenKind::String(string)
-			},
+								separated = true;
+							}
+
+							prev = nch;
 
-			ch => {
-				if ch == '/' {
-					if let Some('/' | '*') = self.src.peek() {
-						if self.eat().unwrap() == '/' {
-							loop {
-								match self.eat() {
-									Some('\n') => break,
-									Some(_) => continue,
-									None => return None,
+							if let 'E' | 'e' = nch {
+								if let Some('-' | '+') = self.src.peek() {
+									prev = self.eat().unwrap();
 								}
 							}
+						}
+
+						let number = self.slice(start.byte, self.location.byte);
+
+						let malformed = if number.matches('.').count() > 1 {
+							Some("multiple decimal points in number literal")
+						} else if number.matches(['E', 'e']).count() > 1 {
+							Some("multiple exponents in number literal")
+						} else if let Some((_, exponent)) = number.split_once(['E', 'e']) {
+							if exponent.contains('.') {
+								Some("decimal point in exponent of number literal")
+							} else if !exponent.contains(|ch: char| ch.is_ascii_digit()) {
+								Some("missing exponent in number literal")
+							} else {
+								None
+							}
+						} else {
+							None
+						};
+
+						if let Some(message) = malformed {
+							return Some(Err(Error {
+								kind: ErrorKind::SyntaxError,
+								location: Some(LocationArea { start, end: self.current }),
+								source: None,
+								message: message.into(),
+							}))
+						}
+
+						let number = if separated {
+							Cow::Owned(number.replace('_', ""))
 						} else {
-							let mut expect_end = false;
-							loop {
-								match self.eat() {
-									Some('*') => { expect_end = true; continue },
-									Some('/') => if expect_end { break },
-									Some(_) => (),
-									None => return Some(Err(Error {
+							number
+						};
+
+						use std::str::FromStr;
+						let integer = if number.contains(['.', 'E', 'e']) {
+							None
+						} else {
+							i64::from_str(&number).ok()
+						};
+
+						match integer {
+							Some(integer) => TokenKind::Integer(integer),
+							None => TokenKind::Number(match f64::from_str(&number) {
+								Ok(number) => number,
+								Err(_) => return Some(Err(Error {
+									kind: ErrorKind::SyntaxError,
+									location: Some(LocationArea { start, end: self.current }),
+									source: None,
+									message: "invalid number literal".into(),
+								})),
+							}),
+						}
+					}
+				},
+				'\u{FEFF}' if start.index == 0 => continue,
+				// a shebang line is ignored at the very start of the input only
+				'#' if start.line == 1 && start.column == 1 && self.src.peek() == Some(&'!') => {
+					loop {
+						match self.eat() {
+							Some('\n' | '\r') => break,
+							Some(_) => continue,
+							None => return None,
+						}
+					}
+
+					continue
+				},
+				'#' if self.src.peek() == Some(&'\\') => {
+					self.eat();
+					let whitespace = self.options.whitespace;
+
+					let first = match self.eat() {
+						Some(first) => first,
+						None => return Some(Err(Error {
+							kind: ErrorKind::IncompleteError,
+							location: Some(self.current.into()),
+							source: None,
+							message: "unexpected end whilst parsing character".into(),
+						})),
+					};
+					let before = self.current;
+
+					let ch = if first == 'u' && self.src.peek() == Some(&'{') {
+						match self.eat_hex_escape(first, before) {
+							Ok(uni) => uni,
+							Err(error) => return Some(Err(error)),
+						}
+					} else if
+						first == '\\' &&
+						self.src.peek().is_some_and(|ch| !whitespace(*ch) && !matches!(ch, ')' | ']' | '}'))
+					{
+						match self.eat_escape() {
+							Ok(ech) => ech,
+							Err(error) => return Some(Err(error)),
+						}
+					} else if first.is_alphabetic() && self.src.peek().is_some_and(|ch| ch.is_alphabetic()) {
+						let mut name = String::from(first);
+						while let Some(ch) = self.src.peek() {
+							if !ch.is_alphanumeric() {
+								break
+							}
+
+							name.push(self.eat().unwrap());
+						}
+
+						match name.as_str() {
+							"alarm" => '\x07',
+							"backspace" => '\x08',
+							"delete" => '\x7f',
+							"escape" => '\x1b',
+							"newline" => '\n',
+							"null" => '\0',
+							"return" => '\r',
+							"space" => ' ',
+							"tab" => '\t',
+
+							_ => return Some(Err(Error {
+								kind: ErrorKind::SyntaxError,
+								location: Some(LocationArea { start, end: self.current }),
+								source: None,
+								message: format!("{:?} is not a valid character name", name),
+							})),
+						}
+					} else {
+						first
+					};
+
+					if let Some(next) = self.src.peek() {
+						if !whitespace(*next) && !matches!(next, ')' | ']' | '}') {
+							return Some(Err(Error {
+								kind: ErrorKind::SyntaxError,
+								location: Some(LocationArea { start, end: self.location }),
+								source: None,
+								message: "more than one character in character literal".into(),
+							}))
+						}
+					}
+
+					TokenKind::Char(ch)
+				},
+				'"' if self.src.peek() == Some(&'"') => {
+					self.eat();
+
+					if self.src.peek() != Some(&'"') {
+						TokenKind::String(Cow::Borrowed(""))
+					} else {
+						self.eat();
+
+						let from = self.location.byte;
+						let mut quotes = 0;
+						while quotes < 3 {
+							match self.eat() {
+								Some('"') => quotes += 1,
+								Some(_) => quotes = 0,
+								None => return Some(Err(Error {
+									kind: ErrorKind::IncompleteError,
+									location: Some(LocationArea { start, end: self.current }),
+									source: None,
+									message: "unterminated string".into(),
+								})),
+							}
+						}
+
+						let string = self.slice(from, self.location.byte - 3);
+						TokenKind::String(Cow::Owned(strip_indent(&string)))
+					}
+				},
+				'"' => {
+					// the contents are only copied once they differ from the source,
+					// from which the run of characters since the last escape is taken
+					let mut string = String::new();
+					let mut from = self.location.byte;
+					let mut parts = Vec::new();
+					loop {
+						if let Some(nch) = self.eat() {
+							if nch == ch {
+								break
+							}
+
+							if matches!(nch, '\\' | '{' | '}') {
+								string.push_str(&self.slice(from, self.current.byte));
+							}
+
+							if nch == '\\' && matches!(self.src.peek(), Some('\n' | '\r')) {
+								// a backslash at the end of a line continues the string
+								// onto the next, without the newline or indentation
+								if self.src.peek() == Some(&'\r') {
+									self.eat();
+								}
+								if self.src.peek() == Some(&'\n') {
+									self.eat();
+								}
+								while let Some(' ' | '\t') = self.src.peek() {
+									self.eat();
+								}
+							} else if nch == '\\' {
+								match self.eat_escape() {
+									Ok(ech) => string.push(ech),
+									Err(error) => return Some(Err(error)),
+								}
+							} else if nch == '{' && self.src.peek() != Some(&'{') {
+								let tokens = match self.eat_interpolation() {
+									Ok(tokens) => tokens,
+									Err(error) => return Some(Err(error)),
+								};
+
+								if !string.is_empty() {
+									parts.push(StringPart::Literal(std::mem::take(&mut string)));
+								}
+
+								parts.push(StringPart::Expression(tokens));
+							} else if nch == '{' || nch == '}' {
+								if self.src.peek() != Some(&nch) {
+									return Some(Err(Error {
 										kind: ErrorKind::SyntaxError,
 										location: Some(self.current.into()),
-										message: "unterminated comment".into(),
-									})),
+										source: None,
+										message: "unmatched '}' in string (use '}}')".into(),
+									}))
 								}
 
-								expect_end = false;
+								self.eat();
+								string.push(nch);
+							}
+
+							if matches!(nch, '\\' | '{' | '}') {
+								from = self.location.byte;
+							}
+						} else {
+							return Some(Err(Error {
+								kind: ErrorKind::IncompleteError,
+								location: Some(self.current.into()),
+								source: None,
+								message: "unterminated string".into(),
+							}))
+						}
+					}
+
+					let rest = self.slice(from, self.current.byte);
+					if parts.is_empty() && string.is_empty() {
+						TokenKind::String(rest)
+					} else {
+						string.push_str(&rest);
+
+						if parts.is_empty() {
+							TokenKind::String(Cow::Owned(string))
+						} else {
+							if !string.is_empty() {
+								parts.push(StringPart::Literal(string));
 							}
+
+							TokenKind::Interpolation(parts)
 						}
+					}
+				},
 
-						// this is stupid
-						return self.next()
+				'/' if self.options.c_comments && self.src.peek() == Some(&'/') => {
+					self.eat();
+					TokenKind::Comment { block: false, text: self.eat_line_comment() }
+				},
+				';' if self.options.lisp_comments =>
+					TokenKind::Comment { block: false, text: self.eat_line_comment() },
+				'/' if self.options.c_comments && self.src.peek() == Some(&'*') => {
+					match self.eat_block_comment(start, ch) {
+						Ok(text) => TokenKind::Comment { block: true, text },
+						Err(error) => return Some(Err(error)),
 					}
-				}
+				},
+				// #tag marks the form after it to be read by the reader for the tag
+				'#' if self.src.peek().is_some_and(|ch| is_symbol_start(*ch)) => {
+					let from = self.location.byte;
+					self.eat();
+					TokenKind::Tag(self.eat_symbol(from))
+				},
+				'#' if self.options.lisp_comments && self.src.peek() == Some(&'|') => {
+					match self.eat_block_comment(start, ch) {
+						Ok(text) => TokenKind::Comment { block: true, text },
+						Err(error) => return Some(Err(error)),
+					}
+				},
 
-				return Some(Err(Error {
-					kind: ErrorKind::SyntaxError,
-					location: Some(self.current.into()),
-					message: format!("unexpected {:?}", ch),
-				}))
-			},
+				ch => {
+					return Some(Err(Error {
+						kind: ErrorKind::SyntaxError,
+						location: Some(self.current.into()),
+						source: None,
+						message: format!("unexpected {:?}", ch),
+					}))
+				},
+			};
+
+			if !self.options.comments && matches!(kind, TokenKind::Comment { .. }) {
+				continue
+			}
+
+			break (start, kind)
 		};
 
 		if
 			kind != TokenKind::LeftParen &&
 			kind != TokenKind::LeftBracket &&
 			kind != TokenKind::LeftBrace &&
-			kind != TokenKind::Dot
+			kind != TokenKind::Dot &&
+			kind != TokenKind::Quote &&
+			kind != TokenKind::Quasiquote &&
+			kind != TokenKind::Unquote &&
+			kind != TokenKind::UnquoteSplice &&
+			kind != TokenKind::Splat &&
+			!matches!(kind, TokenKind::Label(_) | TokenKind::Tag(_) | TokenKind::Comment { .. })
 		{
 			if let Some(ch) = self.src.peek() {
-				let exempt = if let TokenKind::Symbol(_) = kind {
-					*ch == '.'
-				} else {
-					false
-				};
+				// symbols and strings may be followed by a field access
+				let exempt = *ch == '.' && matches!(
+					kind,
+					TokenKind::Symbol(_) | TokenKind::String(_) | TokenKind::Interpolation(_)
+				);
 
-				if !exempt && !ch.is_ascii_whitespace() {
+				// a ; comment may directly follow a token, as in other lisps
+				let comment = *ch == ';' && self.options.lisp_comments;
+				if !exempt && !comment && !(self.options.whitespace)(*ch) {
 					if let ')' | ']' | '}' = ch {} else {
 						return Some(Err(Error {
-							kind: ErrorKind::SyntaxError,
-							location: Some(self.location.into()),
-							message: "expected delimeter".into(),
+							kind: ErrorKind::DelimiterError,
+							location: Some(LocationArea { start, end: self.location }),
+							source: None,
+							message: format!(
+								"expected whitespace or ')' after {}, found {:?}",
+								kind.description(), ch,
+							),
 						}))
 					}
 				}
@@ -294,14 +996,180 @@ impl<'a, I: Iterator<Item = char>> Iterator for Lexer<'a, I> {
 	}
 }
 
+impl<'src, I: Iterator<Item = char>> Iterator for Lexer<'src, I> {
+	type Item = Result<Token<'src>, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		// after an error, a lenient lexer resumes from the next separator
+		if self.failed {
+			self.failed = false;
+
+			while let Some(ch) = self.src.peek() {
+				if (self.options.whitespace)(*ch) || matches!(ch, '(' | ')' | '[' | ']' | '{' | '}') {
+					break
+				}
+
+				self.eat();
+			}
+		}
+
+		let result = self.lex();
+
+		// the source ending early makes anything lexed from it meaningless
+		if let Some(message) = self.src_error.take() {
+			return Some(Err(Error {
+				kind: ErrorKind::IoError,
+				location: Some(self.peeked()),
+				source: self.source.clone(),
+				message,
+			}))
+		}
+
+		let result = match result {
+			Some(result) => result,
+			None if self.options.eof && !self.finished => {
+				self.finished = true;
+
+				return Some(Ok(Token {
+					kind: TokenKind::Eof,
+					location: self.location.into(),
+				}))
+			},
+			None => return None,
+		};
+
+		let mut error = match result {
+			Ok(token) => return Some(Ok(token)),
+			Err(error) => error,
+		};
+
+		if self.options.lenient {
+			self.failed = true;
+
+			if error.kind == ErrorKind::SyntaxError {
+				error.kind = ErrorKind::RecoveredSyntaxError;
+			}
+		}
+
+		if error.source.is_none() {
+			error.source = self.source.clone();
+		}
+
+		Some(Err(error))
+	}
+}
+
+// the characters of a reader, decoded as utf-8; the first error ends them
+pub struct ReadChars<R> {
+	bytes: Bytes<BufReader<R>>,
+	buffer: [u8; 4],
+	bailed: bool,
+	error: Rc<Cell<Option<String>>>,
+}
+
+impl<R: Read> ReadChars<R> {
+	fn fail(&mut self, message: String) -> Option<char> {
+		self.error.set(Some(message));
+		self.bailed = true;
+		None
+	}
+}
+
+impl<R: Read> Iterator for ReadChars<R> {
+	type Item = char;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.bailed {
+			return None
+		}
+
+		for i in 0..4 {
+			match self.bytes.next() {
+				Some(Ok(byte)) => self.buffer[i] = byte,
+				Some(Err(error)) => return self.fail(error.to_string()),
+				None => if i == 0 {
+					return None
+				} else {
+					return self.fail("unterminated utf-8 sequence".into())
+				},
+			}
+
+			if let Ok(ch) = std::str::from_utf8(&self.buffer[..i + 1]) {
+				return ch.chars().next()
+			}
+		}
+
+		self.fail("utf-8 error".into())
+	}
+}
+
+// runs the lexer over the whole source, stopping at the first error
+pub fn tokenize(src: &str) -> Result<Vec<Token<'_>>, Error> {
+	Lexer::from_str(src).collect()
+}
+
+// runs a lenient lexer over the whole source, collecting every error
+pub fn tokenize_all(src: &str) -> (Vec<Token<'_>>, Vec<Error>) {
+	let mut tokens = Vec::new();
+	let mut errors = Vec::new();
+
+	for result in Lexer::from_str(src).lenient() {
+		match result {
+			Ok(token) => tokens.push(token),
+			Err(error) => errors.push(error),
+		}
+	}
+
+	(tokens, errors)
+}
+
+// removes a newline directly after the opening quotes of a multi-line string,
+// and the indentation common to all of its non-blank lines
+fn strip_indent(string: &str) -> String {
+	let string = string
+		.strip_prefix("\r\n")
+		.or_else(|| string.strip_prefix('\n'))
+		.unwrap_or(string);
+
+	let indent = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+	let common = string
+		.lines()
+		.filter(|line| !line.trim().is_empty())
+		.map(indent)
+		.min()
+		.unwrap_or(0);
+
+	string
+		.split('\n')
+		.map(|line| &line[indent(line).min(common)..])
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+// borrowing from the source it was lexed from where possible
 #[derive(Clone, Debug, PartialEq)]
-pub struct Token {
-	pub(super) kind: TokenKind,
+pub struct Token<'src> {
+	pub kind: TokenKind<'src>,
 	pub location: LocationArea,
 }
 
+impl Token<'_> {
+	// the source text of the token, given the source it was lexed from
+	pub fn text<'s>(&self, src: &'s str) -> &'s str {
+		&src[self.location.byte_range(src)]
+	}
+
+	// the token with its contents copied, so that it outlives its source
+	pub fn into_owned(self) -> Token<'static> {
+		Token {
+			kind: self.kind.into_owned(),
+			location: self.location,
+		}
+	}
+}
+
 #[derive(Clone, Debug, PartialEq)]
-pub enum TokenKind {
+pub enum TokenKind<'src> {
 	LeftParen,
 	RightParen,
 	LeftBracket,
@@ -310,10 +1178,363 @@ pub enum TokenKind {
 	RightBrace,
 	Dot,
 
-	Symbol(Symbol),
+	Quote,
+	Quasiquote,
+	Unquote,
+	UnquoteSplice,
+	Splat,
 
-	Number(f32),
-	String(String),
+	Symbol(Cow<'src, str>),
+	Keyword(Cow<'src, str>),
+	Label(Cow<'src, str>),
+	Tag(Cow<'src, str>),
+
+	Integer(i64),
+	Number(f64),
+	Char(char),
+	String(Cow<'src, str>),
+	Interpolation(Vec<StringPart<'src>>),
 	Boolean(bool),
 	Nil,
+
+	Comment { block: bool, text: Cow<'src, str> },
+	Eof,
+}
+
+impl TokenKind<'_> {
+	pub fn into_owned(self) -> TokenKind<'static> {
+		let owned = |text: Cow<str>| Cow::Owned(text.into_owned());
+
+		match self {
+			Self::LeftParen => TokenKind::LeftParen,
+			Self::RightParen => TokenKind::RightParen,
+			Self::LeftBracket => TokenKind::LeftBracket,
+			Self::RightBracket => TokenKind::RightBracket,
+			Self::LeftBrace => TokenKind::LeftBrace,
+			Self::RightBrace => TokenKind::RightBrace,
+			Self::Dot => TokenKind::Dot,
+
+			Self::Quote => TokenKind::Quote,
+			Self::Quasiquote => TokenKind::Quasiquote,
+			Self::Unquote => TokenKind::Unquote,
+			Self::UnquoteSplice => TokenKind::UnquoteSplice,
+			Self::Splat => TokenKind::Splat,
+
+			Self::Symbol(symbol) => TokenKind::Symbol(owned(symbol)),
+			Self::Keyword(symbol) => TokenKind::Keyword(owned(symbol)),
+			Self::Label(symbol) => TokenKind::Label(owned(symbol)),
+			Self::Tag(symbol) => TokenKind::Tag(owned(symbol)),
+
+			Self::Integer(integer) => TokenKind::Integer(integer),
+			Self::Number(number) => TokenKind::Number(number),
+			Self::Char(ch) => TokenKind::Char(ch),
+			Self::String(string) => TokenKind::String(owned(string)),
+			Self::Interpolation(parts) => TokenKind::Interpolation(
+				parts.into_iter().map(StringPart::into_owned).collect(),
+			),
+			Self::Boolean(boolean) => TokenKind::Boolean(boolean),
+			Self::Nil => TokenKind::Nil,
+
+			Self::Comment { block, text } => TokenKind::Comment { block, text: owned(text) },
+			Self::Eof => TokenKind::Eof,
+		}
+	}
+
+	fn description(&self) -> &'static str {
+		match self {
+			Self::RightParen => "')'",
+			Self::RightBracket => "']'",
+			Self::RightBrace => "'}'",
+			Self::Symbol(_) => "symbol",
+			Self::Keyword(_) => "keyword",
+			Self::Label(_) => "argument name",
+			Self::Tag(_) => "reader tag",
+			Self::Integer(_) => "integer literal",
+			Self::Number(_) => "number literal",
+			Self::Char(_) => "character literal",
+			Self::String(_) | Self::Interpolation(_) => "string literal",
+			Self::Boolean(_) => "boolean literal",
+			Self::Nil => "nil",
+			_ => "token",
+		}
+	}
+}
+
+// written as the source which would produce the token
+impl fmt::Display for TokenKind<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::LeftParen => write!(f, "("),
+			Self::RightParen => write!(f, ")"),
+			Self::LeftBracket => write!(f, "["),
+			Self::RightBracket => write!(f, "]"),
+			Self::LeftBrace => write!(f, "{{"),
+			Self::RightBrace => write!(f, "}}"),
+			Self::Dot => write!(f, "."),
+
+			Self::Quote => write!(f, "'"),
+			Self::Quasiquote => write!(f, "`"),
+			Self::Unquote => write!(f, ","),
+			Self::UnquoteSplice => write!(f, ",@"),
+			Self::Splat => write!(f, ".."),
+
+			Self::Symbol(symbol) => write!(f, "{}", symbol),
+			Self::Keyword(symbol) => write!(f, ":{}", symbol),
+			Self::Label(symbol) => write!(f, "{}:", symbol),
+			Self::Tag(symbol) => write!(f, "#{}", symbol),
+
+			Self::Integer(integer) => write!(f, "{}", integer),
+			Self::Number(number) if number.is_nan() => write!(f, "nan"),
+			Self::Number(number) => write!(f, "{:?}", number),
+			Self::Char(ch) => write!(f, "#\\{}", ch.escape_debug()),
+			Self::String(string) => write!(f, "{:?}", string),
+			Self::Interpolation(parts) => {
+				write!(f, "\"")?;
+				for part in parts {
+					match part {
+						StringPart::Literal(string) => write!(
+							f,
+							"{}",
+							string.escape_debug().to_string().replace('{', "{{").replace('}', "}}"),
+						)?,
+						StringPart::Expression(tokens) => {
+							let tokens = tokens
+								.iter()
+								.map(|token| token.kind.to_string())
+								.collect::<Vec<_>>();
+							write!(f, "{{{}}}", tokens.join(" "))?;
+						},
+					}
+				}
+				write!(f, "\"")
+			},
+			Self::Boolean(boolean) => write!(f, "{}", boolean),
+			Self::Nil => write!(f, "nil"),
+
+			Self::Comment { block: false, text } => write!(f, "//{}", text),
+			Self::Comment { block: true, text } => write!(f, "/*{}*/", text),
+			Self::Eof => write!(f, "end of input"),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StringPart<'src> {
+	Literal(String),
+	Expression(Vec<Token<'src>>),
+}
+
+impl StringPart<'_> {
+	pub fn into_owned(self) -> StringPart<'static> {
+		match self {
+			Self::Literal(string) => StringPart::Literal(string),
+			Self::Expression(tokens) => StringPart::Expression(
+				tokens.into_iter().map(Token::into_owned).collect(),
+			),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ Lexer, TokenKind, tokenize, tokenize_all };
+	use crate::{ Error, ErrorKind };
+
+	fn kinds(src: &str) -> Vec<TokenKind<'_>> {
+		tokenize(src).unwrap().into_iter().map(|token| token.kind).collect()
+	}
+
+	fn error(src: &str) -> Error {
+		tokenize(src).unwrap_err()
+	}
+
+	#[test]
+	fn delimiter_error_at_character() {
+		let src = "(1 2)3";
+		let error = error(src);
+		let location = error.location.unwrap();
+
+		assert_eq!(error.kind, ErrorKind::DelimiterError);
+		// spanning the closer and the character after it, rather than the end
+		assert_eq!(location.end.byte, 5);
+		assert_eq!(location.end.column, 6);
+		assert_eq!(&src[location.byte_range(src)], ")3");
+	}
+
+	#[test]
+	fn first_column_is_one() {
+		let start = error("@").location.unwrap().start;
+		assert_eq!((start.line, start.column), (1, 1));
+
+		// a carriage return before a newline is part of the line break
+		let start = error("a\r\nb\r\n@").location.unwrap().start;
+		assert_eq!((start.line, start.column, start.byte), (3, 1, 6));
+
+		// as is a lone carriage return
+		let start = error("a\rb\r@").location.unwrap().start;
+		assert_eq!((start.line, start.column), (3, 1));
+
+		// a byte order mark takes no column, though it takes bytes
+		let start = error("\u{feff}@").location.unwrap().start;
+		assert_eq!((start.line, start.column, start.byte), (1, 1, 3));
+	}
+
+	#[test]
+	fn signs() {
+		let symbol = |name: &'static str| TokenKind::Symbol(name.into());
+		assert_eq!(kinds("(- 3 2)"), [
+			TokenKind::LeftParen, symbol("-"), TokenKind::Integer(3), TokenKind::Integer(2),
+			TokenKind::RightParen,
+		]);
+		assert_eq!(kinds("(+ 1 2)"), [
+			TokenKind::LeftParen, symbol("+"), TokenKind::Integer(1), TokenKind::Integer(2),
+			TokenKind::RightParen,
+		]);
+
+		// a sign only begins a number directly before a digit or point
+		assert_eq!(kinds("-5"), [ TokenKind::Integer(-5) ]);
+		assert_eq!(kinds("-.5 +3"), [ TokenKind::Number(-0.5), TokenKind::Integer(3) ]);
+		assert_eq!(kinds("- 5"), [ symbol("-"), TokenKind::Integer(5) ]);
+	}
+
+	#[test]
+	fn dotted_access() {
+		let symbol = |name: &'static str| TokenKind::Symbol(name.into());
+		assert_eq!(kinds("a.b"), [ symbol("a"), TokenKind::Dot, symbol("b") ]);
+		// as between names using the other characters symbols may hold
+		assert_eq!(kinds("set-x!.null?"), [ symbol("set-x!"), TokenKind::Dot, symbol("null?") ]);
+	}
+
+	#[test]
+	fn separators() {
+		assert_eq!(kinds("1_0e1_0"), [ TokenKind::Number(1e11) ]);
+		assert_eq!(kinds("1_000.5_5"), [ TokenKind::Number(1000.55) ]);
+		assert_eq!(kinds("0x1_F 0xFF_FF"), [ TokenKind::Integer(31), TokenKind::Integer(65535) ]);
+
+		// a separator must come between two digits, and is reported where it is
+		for (src, column) in [ ("1e_5", 3), ("0x_ff", 3), ("1__0", 2), ("1_", 2) ] {
+			let error = error(src);
+			assert_eq!(error.kind, ErrorKind::SyntaxError, "{}", src);
+			assert_eq!(error.location.map(|location| location.start.column), Some(column), "{}", src);
+		}
+	}
+
+	#[test]
+	fn escapes() {
+		let string = |string: &'static str| TokenKind::String(string.into());
+		assert_eq!(kinds(r#""\"\'\a\e\f\v""#), [ string("\"'\x07\x1b\x0c\x0b") ]);
+		assert_eq!(kinds(r"#\\' #\\a #\\e #\\f #\\v"), [
+			TokenKind::Char('\''), TokenKind::Char('\x07'), TokenKind::Char('\x1b'),
+			TokenKind::Char('\x0c'), TokenKind::Char('\x0b'),
+		]);
+		// a quote within a character literal needs no escape, but may have one
+		assert_eq!(kinds(r#"#\" #\\""#), [ TokenKind::Char('"'), TokenKind::Char('"') ]);
+
+		// a backslash ending a line skips the line break and indentation after it
+		assert_eq!(kinds("\"a \\\n\t  b\""), [ string("a b") ]);
+		assert_eq!(kinds("\"a \\\r\n  b\""), [ string("a b") ]);
+	}
+
+	#[test]
+	fn truncated_escapes() {
+		// spanning from the backslash beginning the escape to the end
+		for src in [ "\"ab\\", "\"ab\\x4", "\"ab\\u{4", "#\\\\x4" ] {
+			let error = error(src);
+			let location = error.location.unwrap();
+			assert_eq!(error.kind, ErrorKind::IncompleteError, "{:?}", src);
+			assert_eq!(location.start.byte, src.rfind('\\').unwrap(), "{:?}", src);
+			assert_eq!(location.end.byte, src.len() - 1, "{:?}", src);
+		}
+	}
+
+	#[test]
+	fn control_escapes() {
+		for (escape, ch) in [
+			(r"\b", '\x08'), (r"\f", '\x0c'), (r"\v", '\x0b'), (r"\a", '\x07'),
+			(r#"\""#, '"'), (r"\'", '\''),
+		] {
+			let src = format!("\"{}\"", escape);
+			assert_eq!(kinds(&src), [ TokenKind::String(ch.to_string().into()) ], "{}", escape);
+		}
+	}
+
+	#[test]
+	fn byte_locations() {
+		let src = "(\"😀 é\" x)";
+		let tokens = tokenize(src).unwrap();
+
+		// the string takes one character more than its bytes would suggest
+		let string = tokens[1].location;
+		assert_eq!((string.start.index, string.end.index), (1, 5));
+		assert_eq!((string.start.byte, string.end.byte), (1, 9));
+		assert_eq!(tokens[1].text(src), "\"😀 é\"");
+
+		let symbol = tokens[2].location;
+		assert_eq!((symbol.start.index, symbol.start.byte, symbol.start.column), (7, 11, 8));
+		assert_eq!(tokens[2].text(src), "x");
+		assert_eq!(tokens[3].text(src), ")");
+	}
+
+	// a small xorshift generator, so that every run tries the same inputs
+	struct Random(u64);
+
+	impl Random {
+		fn next(&mut self) -> u64 {
+			self.0 ^= self.0 << 13;
+			self.0 ^= self.0 >> 7;
+			self.0 ^= self.0 << 17;
+			self.0
+		}
+
+		fn below(&mut self, n: usize) -> usize {
+			(self.next() % n as u64) as usize
+		}
+	}
+
+	// characters which mean something to the lexer, more often than any other
+	const SPECIAL: &[char] = &[
+		'(', ')', '[', ']', '{', '}', '"', '\'', '`', ',', '@', '#', '\\', ';', '|',
+		'/', '*', '.', ':', '-', '+', '0', '1', 'e', 'x', 'u', 'n', ' ', '\t', '\n',
+		'\r', '\u{feff}', 'é', '😀',
+	];
+
+	fn source(random: &mut Random) -> String {
+		(0..random.below(40))
+			.map(|_| match random.below(4) {
+				0 => char::from_u32(random.below(0x11_0000) as u32).unwrap_or('?'),
+				_ => SPECIAL[random.below(SPECIAL.len())],
+			})
+			.collect()
+	}
+
+	#[test]
+	fn random_sources() {
+		let mut random = Random(0x2545_f491_4f6c_dd1d);
+
+		for _ in 0..20_000 {
+			let src = source(&mut random);
+
+			let _ = tokenize(&src);
+			let (tokens, _) = tokenize_all(&src);
+			for token in tokens {
+				let _ = token.text(&src);
+			}
+
+			let _ = crate::parse_all(&src);
+			let _ = crate::format(&src);
+		}
+	}
+
+	#[test]
+	fn random_bytes() {
+		let mut random = Random(0x9e37_79b9_7f4a_7c15);
+
+		for _ in 0..20_000 {
+			let bytes = (0..random.below(40))
+				.map(|_| random.next() as u8)
+				.collect::<Vec<_>>();
+
+			Lexer::from_reader(&bytes[..]).lenient().for_each(drop);
+		}
+	}
 }
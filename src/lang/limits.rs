@@ -0,0 +1,182 @@
+use super::{ Error, ErrorKind };
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{ Duration, Instant };
+
+// bounds on what a program may do, for running one which is not trusted, each
+// unbounded unless set; copies share what has been used, so that the limits
+// hold across every environment within a program
+#[derive(Clone, Default)]
+pub struct Limits {
+	steps: Option<u64>,
+	time: Option<Duration>,
+	depth: Option<usize>,
+	no_io: bool,
+	used: Rc<Used>,
+}
+
+#[derive(Default)]
+struct Used {
+	steps: Cell<u64>,
+	start: Cell<Option<Instant>>,
+	depth: Cell<usize>,
+}
+
+impl Limits {
+	// the expressions evaluated by each evaluation of a program, counting each
+	// iteration of a loop or tail call as another, as well as each item a
+	// provided function works through
+	pub fn with_steps(mut self, steps: u64) -> Self {
+		self.steps = Some(steps);
+		self
+	}
+
+	// the time spent on each evaluation of a program
+	pub fn with_time(mut self, time: Duration) -> Self {
+		self.time = Some(time);
+		self
+	}
+
+	// the expressions being evaluated within one another, which each call not in
+	// tail position adds to
+	pub fn with_depth(mut self, depth: usize) -> Self {
+		self.depth = Some(depth);
+		self
+	}
+
+	// refuses the functions reading files and standard input, and writing files
+	// and standard output, as well as imports
+	pub fn without_io(mut self) -> Self {
+		self.no_io = true;
+		self
+	}
+
+	pub fn allows_io(&self) -> bool {
+		!self.no_io
+	}
+
+	// begins an evaluation of a program, from which steps and time are counted
+	pub(crate) fn start(&self) {
+		self.used.steps.set(0);
+		self.used.start.set(Some(Instant::now()));
+	}
+
+	pub(crate) fn step(&self) -> Result<(), Error> {
+		self.step_by(1)
+	}
+
+	// counts many steps at once, for work done by a provided function (such as
+	// the items of a range)
+	pub(crate) fn step_by(&self, steps: u64) -> Result<(), Error> {
+		let steps = self.used.steps.get().saturating_add(steps);
+		self.used.steps.set(steps);
+
+		if let Some(limit) = self.steps.filter(|limit| steps > *limit) {
+			return Err(exceeded(format!("evaluation exceeded {} steps", limit)))
+		}
+
+		let start = self.used.start.get();
+		if let (Some(limit), Some(start)) = (self.time, start) {
+			if start.elapsed() > limit {
+				return Err(exceeded(format!("evaluation exceeded {:?}", limit)))
+			}
+		}
+
+		Ok(())
+	}
+
+	// enters an expression, which must be left once evaluated (even if that
+	// fails)
+	pub(crate) fn enter(&self) -> Result<(), Error> {
+		let depth = self.used.depth.get() + 1;
+
+		match self.depth {
+			Some(limit) if depth > limit =>
+				Err(exceeded(format!("evaluation exceeded a depth of {}", limit))),
+			_ => {
+				self.used.depth.set(depth);
+				Ok(())
+			},
+		}
+	}
+
+	pub(crate) fn leave(&self) {
+		self.used.depth.set(self.used.depth.get() - 1);
+	}
+}
+
+fn exceeded(message: String) -> Error {
+	Error {
+		kind: ErrorKind::ResourceLimit,
+		location: None, // todo
+		source: None,
+		message,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Limits;
+	use crate::{ eval, ErrorKind };
+	use crate::runtime::Environment;
+
+	fn run(src: &str, limits: Limits) -> Result<String, ErrorKind> {
+		let mut env = Environment { limits, ..Default::default() };
+		eval(src, Some(&mut env)).map(|value| value.to_string()).map_err(|error| error.kind)
+	}
+
+	#[test]
+	fn steps() {
+		let limits = Limits::default().with_steps(1000);
+		assert_eq!(run("(loop () (recur))", limits.clone()), Err(ErrorKind::ResourceLimit));
+		assert_eq!(run("(reduce + (range 100000))", limits.clone()), Err(ErrorKind::ResourceLimit));
+		assert_eq!(run("(range 1000000000000)", limits.clone()), Err(ErrorKind::ResourceLimit));
+
+		// each evaluation is counted afresh
+		assert_eq!(run("(reduce + (range 100))", limits), Ok("4950".into()));
+	}
+
+	#[test]
+	fn uncaught() {
+		let limits = Limits::default().with_steps(1000);
+		assert_eq!(run("(try (loop () (recur)) (catch e e))", limits), Err(ErrorKind::ResourceLimit));
+	}
+
+	#[test]
+	fn depth() {
+		let limits = Limits::default().with_depth(20);
+		let src = "(def depth (n) (add 1 (depth (add n 1)))) (depth 0)";
+		assert_eq!(run(src, limits.clone()), Err(ErrorKind::ResourceLimit));
+
+		// calls in tail position do not add to it
+		let src = "(def count (n) (if (lt n 1000) (count (add n 1)) n)) (count 0)";
+		assert_eq!(run(src, limits), Ok("1000".into()));
+	}
+
+	#[test]
+	fn output() {
+		let limits = Limits::default().without_io();
+		assert_eq!(run("(print 1)", limits.clone()), Err(ErrorKind::ResourceLimit));
+		assert_eq!(run("(io/pr 1)", limits.clone()), Err(ErrorKind::ResourceLimit));
+
+		// though the host may give a print of its own
+		let mut env = Environment { limits, ..Default::default() };
+		env.register_fn("print", |_| Ok(crate::Value::nil()));
+		assert!(eval("(print 1)", Some(&mut env)).is_ok());
+	}
+
+	#[cfg(feature = "io")]
+	#[test]
+	fn io() {
+		let limits = Limits::default().without_io();
+		assert_eq!(run("(slurp \"Cargo.toml\")", limits.clone()), Err(ErrorKind::ResourceLimit));
+		assert_eq!(run("(io/spit \"out.txt\" 1)", limits), Err(ErrorKind::ResourceLimit));
+	}
+
+	#[test]
+	fn import() {
+		let limits = Limits::default().without_io();
+		assert_eq!(run("(import \"examples/factorial\")", limits), Err(ErrorKind::ResourceLimit));
+	}
+}
@@ -0,0 +1,164 @@
+use super::{ Value, LocationArea };
+
+use std::fmt;
+use std::hash::{ Hash, Hasher };
+use std::rc::Rc;
+
+// a persistent singly-linked list, whose cells are shared between the lists
+// built upon them, so that adding to (or taking from) the front is constant
+// time and leaves the original as it was
+#[derive(Clone, Default)]
+pub struct List(Option<Rc<Cell>>);
+
+struct Cell {
+	head: Value,
+	tail: List,
+	// where the list starting at this cell was written, if it was read from
+	// source (boxed, as few cells have one)
+	location: Option<Box<LocationArea>>,
+}
+
+impl List {
+	pub fn new() -> Self {
+		Self(None)
+	}
+
+	// the list with the value in front of this one, sharing its cells
+	pub fn cons(&self, head: Value) -> Self {
+		Self(Some(Rc::new(Cell { head, tail: self.clone(), location: None })))
+	}
+
+	// the list as written at a location in source, which errors in evaluating it
+	// are reported at; the location is not part of its value, so is neither
+	// compared nor hashed
+	pub fn located(mut self, location: LocationArea) -> Self {
+		match self.0.take() {
+			Some(cell) => {
+				let mut cell = Rc::try_unwrap(cell).unwrap_or_else(|cell| Cell {
+					head: cell.head.clone(),
+					tail: cell.tail.clone(),
+					location: None,
+				});
+				cell.location = Some(Box::new(location));
+
+				Self(Some(Rc::new(cell)))
+			},
+			None => Self::new(),
+		}
+	}
+
+	pub fn location(&self) -> Option<LocationArea> {
+		self.0.as_ref().and_then(|cell| cell.location.as_deref().copied())
+	}
+
+	pub fn first(&self) -> Option<&Value> {
+		self.0.as_ref().map(|cell| &cell.head)
+	}
+
+	// the list after the first value, which is empty for an empty list
+	pub fn rest(&self) -> Self {
+		self.0.as_ref().map_or_else(Self::new, |cell| cell.tail.clone())
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0.is_none()
+	}
+
+	// counts the values, so takes time in proportion to them
+	pub fn len(&self) -> usize {
+		self.iter().count()
+	}
+
+	pub fn iter(&self) -> Iter<'_> {
+		Iter(self.0.as_deref())
+	}
+
+	// the values, moved out of any cells not shared with another list (and
+	// copied from those which are)
+	pub fn into_vec(mut self) -> Vec<Value> {
+		let mut values = Vec::new();
+		while let Some(cell) = self.0.take() {
+			match Rc::try_unwrap(cell) {
+				Ok(mut cell) => {
+					values.push(std::mem::take(&mut cell.head));
+					self = std::mem::take(&mut cell.tail);
+				},
+				Err(cell) => {
+					values.extend(List(Some(cell)).iter().cloned());
+					break
+				},
+			}
+		}
+
+		values
+	}
+}
+
+// cells are dropped in turn rather than recursively, so that dropping a long
+// list does not overflow the stack
+impl Drop for List {
+	fn drop(&mut self) {
+		let mut next = self.0.take();
+		while let Some(cell) = next {
+			next = match Rc::try_unwrap(cell) {
+				Ok(mut cell) => cell.tail.0.take(),
+				Err(_) => break,
+			};
+		}
+	}
+}
+
+pub struct Iter<'a>(Option<&'a Cell>);
+
+impl<'a> Iterator for Iter<'a> {
+	type Item = &'a Value;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let cell = self.0?;
+		self.0 = cell.tail.0.as_deref();
+
+		Some(&cell.head)
+	}
+}
+
+impl<'a> IntoIterator for &'a List {
+	type Item = &'a Value;
+	type IntoIter = Iter<'a>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.iter()
+	}
+}
+
+impl FromIterator<Value> for List {
+	fn from_iter<I: IntoIterator<Item = Value>>(iter: I) -> Self {
+		let values = iter.into_iter().collect::<Vec<_>>();
+		values.into_iter().rev().fold(Self::new(), |list, value| list.cons(value))
+	}
+}
+
+impl From<Vec<Value>> for List {
+	fn from(values: Vec<Value>) -> Self {
+		values.into_iter().collect()
+	}
+}
+
+impl PartialEq for List {
+	fn eq(&self, other: &Self) -> bool {
+		self.iter().eq(other.iter())
+	}
+}
+
+impl Hash for List {
+	fn hash<H: Hasher>(&self, hasher: &mut H) {
+		for value in self {
+			value.hash(hasher);
+		}
+	}
+}
+
+impl fmt::Debug for List {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_list().entries(self).finish()
+	}
+}
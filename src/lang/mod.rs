@@ -1,11 +1,26 @@
+pub mod ast;
+pub mod expand;
 pub mod lexer;
+pub mod limits;
+pub mod list;
+pub mod module;
 pub mod parser;
+pub mod pattern;
 pub mod runtime;
+pub mod set;
+pub mod stream;
+pub mod visit;
 
 mod stdlib;
+#[cfg(feature = "serde")]
+mod serialize;
 
+use std::any::Any;
+use std::fmt;
 use std::hash::{ Hash, Hasher };
-use std::io::{ Bytes, Read };
+use std::io::Read;
+use std::ops::Range;
+use std::rc::Rc;
 
 #[allow(unused)]
 pub fn eval(
@@ -14,128 +29,111 @@ pub fn eval(
 ) -> Result<Value, Error> {
 	let mut blank = runtime::Environment::default();
 	let env = env.unwrap_or(&mut blank);
-	env.functions.extend(stdlib::index().into_iter());
+	provide(env);
+	env.limits.start();
 
-	let mut chars = src.chars();
-	let lexer = lexer::Lexer::new(&mut chars);
-
-	let mut lexer_error = None;
-	let result = parser::parse(
-		&mut lexer.map_while(|result| match result {
-			Ok(token) => Some(token),
-			Err(error) => {
-				lexer_error = Some(error);
-				None
-			},
-		}),
-	);
+	let tokens = lexer::tokenize(src)?;
+	let program = parser::Parser::new(&mut tokens.into_iter().map(Ok)).parse_program()?;
+
+	run_program(&program, env)
+}
 
-	if let Some(error) = lexer_error {
-		return Err(error)
+// parses a whole source for evaluating later (with eval_ast), or gives every
+// error found in it
+pub fn parse(src: &str) -> Result<ast::Ast, Vec<Error>> {
+	match parse_all(src) {
+		(program, errors) if errors.is_empty() => Ok(program),
+		(_, errors) => Err(errors),
 	}
+}
 
-	let parsed = result?;
+// parses as much of a source as possible, skipping past each error found to
+// continue (as for checking a source, rather than evaluating it)
+pub fn parse_all(src: &str) -> (ast::Ast, Vec<Error>) {
+	parse_tokens(&mut lexer::Lexer::from_str(src).lenient())
+}
 
-	let mut last = None;
-	for value in parsed {
-		last = Some(runtime::run(value, env)?);
+// gives a source in canonical form (keeping its comments), or every error found
+// in it
+pub fn format(src: &str) -> Result<String, Vec<Error>> {
+	let mut comments = Vec::new();
+	let mut tokens = lexer::Lexer::from_str(src)
+		.lenient()
+		.with_comments()
+		.inspect(|token| if let Ok(lexer::Token {
+			kind: lexer::TokenKind::Comment { .. },
+			location,
+		}) = token {
+			comments.push(*location);
+		});
+
+	match parse_tokens(&mut tokens) {
+		(program, errors) if errors.is_empty() =>
+			Ok(program.to_source_with_comments(src, &comments)),
+		(_, errors) => Err(errors),
 	}
+}
 
-	Ok(last.unwrap_or(Value::nil()))
+fn parse_tokens<'src>(
+	tokens: &mut impl Iterator<Item = Result<lexer::Token<'src>, Error>>,
+) -> (ast::Ast, Vec<Error>) {
+	let mut parser = parser::Parser::new(tokens).lenient();
+
+	let (program, mut errors) = match parser.parse_program() {
+		Ok(program) => (program, Vec::new()),
+		Err(error) => (Vec::new(), vec![ error ]),
+	};
+	errors.splice(0..0, parser.take_errors());
+
+	(ast::Ast::program(program), errors)
 }
 
-pub fn eval_read(
-	src: &mut impl Read,
+pub fn eval_ast(
+	ast: &ast::Ast,
 	env: Option<&mut runtime::Environment>,
 ) -> Result<Value, Error> {
 	let mut blank = runtime::Environment::default();
 	let env = env.unwrap_or(&mut blank);
-	env.functions.extend(stdlib::index().into_iter());
-
-	struct Utf8Decoder<R> {
-		bytes: Bytes<R>,
-		buffer: [u8; 4],
-		bailed: bool,
-	}
-
-	impl<R: Read> Iterator for Utf8Decoder<R> {
-		type Item = Result<char, String>;
-
-		fn next(&mut self) -> Option<Self::Item> {
-			if self.bailed {
-				return None
-			}
-
-			for i in 0..4 {
-				match self.bytes.next() {
-					Some(Ok(byte)) => self.buffer[i] = byte,
-					Some(Err(error)) => {
-						self.bailed = true;
-						return Some(Err(error.to_string()))
-					},
-					None => if i == 0 {
-						return None
-					} else {
-						self.bailed = true;
-						return Some(Err("unterminated utf-8 sequence".into()))
-					},
-				}
+	provide(env);
+	env.limits.start();
 
-				if let Ok(ch) = std::str::from_utf8(&self.buffer[..i + 1]) {
-					return Some(Ok(ch.chars().next().unwrap()))
-				}
-			}
-
-			self.bailed = true;
-			Some(Err("utf-8 error".into()))
-		}
+	match &ast.kind {
+		ast::AstKind::Program(program) => run_program(program, env),
+		_ => run_program(std::slice::from_ref(ast), env),
 	}
+}
 
-	let decoder = Utf8Decoder {
-		bytes: src.bytes(),
-		buffer: [0; 4],
-		bailed: false,
-	};
+pub fn eval_read(
+	src: &mut impl Read,
+	env: Option<&mut runtime::Environment>,
+) -> Result<Value, Error> {
+	let mut blank = runtime::Environment::default();
+	let env = env.unwrap_or(&mut blank);
+	provide(env);
+	env.limits.start();
 
-	let mut decoder_error = None;
-	let mut mw = decoder.map_while(|result| match result {
-		Ok(ch) => Some(ch),
-		Err(error) => {
-			decoder_error = Some(error);
-			None
-		},
-	});
-
-	let lexer = lexer::Lexer::new(&mut mw);
-
-	let mut lexer_error = None;
-	let result = parser::parse(
-		&mut lexer.map_while(|result| match result {
-			Ok(token) => Some(token),
-			Err(error) => {
-				lexer_error = Some(error);
-				None
-			},
-		}),
-	);
+	let tokens = lexer::Lexer::from_reader(src).collect::<Result<Vec<_>, _>>()?;
+	let program = parser::Parser::new(&mut tokens.into_iter().map(Ok)).parse_program()?;
 
-	if let Some(error) = decoder_error {
-		return Err(Error {
-			kind: ErrorKind::IoError,
-			location: None,
-			message: error,
-		})
-	}
+	run_program(&program, env)
+}
 
-	if let Some(error) = lexer_error {
-		return Err(error)
+// adds the provided functions to an environment, leaving any already there in
+// place: those defined or registered under the same name, and those provided by
+// an earlier evaluation (so that each stays the same function throughout)
+fn provide(env: &mut runtime::Environment) {
+	for (name, function) in stdlib::index_within(&env.limits) {
+		env.functions.entry(name).or_insert(function);
 	}
+}
 
-	let parsed = result?;
-
+// evaluates each expression of a program in turn, expanding the macros within
+// it first, so that each may use the macros defined by those before it
+fn run_program(program: &[ast::Ast], env: &mut runtime::Environment) -> Result<Value, Error> {
 	let mut last = None;
-	for value in parsed {
-		last = Some(runtime::run(value, env)?);
+	for ast in program {
+		let ast = expand::expand(ast, env)?;
+		last = Some(runtime::run(ast.to_value_with(&env.readers)?, env)?);
 	}
 
 	Ok(last.unwrap_or(Value::nil()))
@@ -143,35 +141,128 @@ pub fn eval_read(
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value {
-	Number(f32),
+	Integer(i64),
+	Number(f64),
+	Char(char),
 	String(String),
 	Boolean(bool),
-	List(Vec<Self>),
+	List(list::List),
+	Vector(Vec<Self>),
+	// entries in insertion order, with no two keys equal
+	Map(Vec<(Self, Self)>),
+	// items in insertion order, with no two equal
+	Set(set::Set),
 	Symbol(Symbol),
+	Keyword(Symbol),
+	Function(Rc<runtime::Function>),
+	// a value marked by reduced, which ends a reduce early with it
+	Reduced(Box<Self>),
+	Foreign(Foreign),
 }
 
 impl Value {
 	pub fn nil() -> Self {
-		Self::List(Vec::new())
+		Self::List(list::List::new())
+	}
+
+	// a value of the host, which programs can only pass around
+	pub fn foreign<T: Any>(value: T) -> Self {
+		Self::Foreign(Foreign(Rc::new(value)))
+	}
+
+	// the host value held, if this is a foreign value of the given type
+	pub fn downcast<T: Any>(&self) -> Option<&T> {
+		match self {
+			Self::Foreign(foreign) => foreign.0.downcast_ref(),
+			_ => None,
+		}
+	}
+
+	// a map of the given entries, where an entry replaces any earlier one with
+	// the same key (but keeps its position)
+	pub fn map(entries: impl IntoIterator<Item = (Self, Self)>) -> Self {
+		let mut map: Vec<(Self, Self)> = Vec::new();
+		for (key, value) in entries {
+			match map.iter_mut().find(|(existing, _)| *existing == key) {
+				Some(entry) => entry.1 = value,
+				None => map.push((key, value)),
+			}
+		}
+
+		Self::Map(map)
+	}
+
+	// a set of the given items, where an item equal to an earlier one is left
+	// out; only hashable values (those not holding a function) may be items
+	pub fn set(items: impl IntoIterator<Item = Self>) -> Result<Self, Error> {
+		let mut set = set::Set::new();
+		for item in items {
+			if let Some(unhashable) = item.unhashable() {
+				return Err(Error {
+					kind: ErrorKind::TypeError,
+					location: None,
+					source: None,
+					message: format!("{} cannot be in a set, as it is not hashable", unhashable.type_name()),
+				})
+			}
+
+			set.insert(item);
+		}
+
+		Ok(Self::Set(set))
+	}
+
+	// the first value within this one (or this one itself) which cannot be
+	// hashed by its contents
+	fn unhashable(&self) -> Option<&Self> {
+		match self {
+			Self::List(items) => items.iter().find_map(Self::unhashable),
+			Self::Vector(items) => items.iter().find_map(Self::unhashable),
+			Self::Set(set) => set.iter().find_map(Self::unhashable),
+			Self::Map(map) => map
+				.iter()
+				.find_map(|(key, value)| key.unhashable().or_else(|| value.unhashable())),
+			Self::Function(_) => Some(self),
+			Self::Reduced(value) => value.unhashable(),
+			_ => None,
+		}
 	}
 
 	pub fn type_name(&self) -> &'static str {
 		match self {
+			Self::Integer(_) => "integer",
 			Self::Number(_) => "number",
+			Self::Char(_) => "char",
 			Self::String(_) => "string",
 			Self::Boolean(_) => "boolean",
-			Self::List(list) => if list.len() > 0 { "list" } else { "nil" },
+			Self::List(list) => if !list.is_empty() { "list" } else { "nil" },
+			Self::Vector(_) => "vector",
+			Self::Map(_) => "map",
+			Self::Set(_) => "set",
 			Self::Symbol(_) => "symbol",
+			Self::Keyword(_) => "keyword",
+			Self::Function(_) => "function",
+			Self::Reduced(_) => "reduced",
+			Self::Foreign(_) => "foreign",
 		}
 	}
 
 	pub fn truthy(&self) -> bool {
 		match self {
-			Self::Number(number) => *number != 0f32,
-			Self::String(string) => string.len() > 0,
+			Self::Integer(integer) => *integer != 0,
+			Self::Number(number) => *number != 0f64,
+			Self::Char(_) => true,
+			Self::String(string) => !string.is_empty(),
 			Self::Boolean(boolean) => *boolean,
-			Self::List(list) => list.len() > 0,
+			Self::List(list) => !list.is_empty(),
+			Self::Vector(vector) => !vector.is_empty(),
+			Self::Map(map) => !map.is_empty(),
+			Self::Set(set) => !set.is_empty(),
 			Self::Symbol(_) => true,
+			Self::Keyword(_) => true,
+			Self::Function(_) => true,
+			Self::Reduced(_) => true,
+			Self::Foreign(_) => true,
 		}
 	}
 }
@@ -182,16 +273,96 @@ impl Default for Value {
 	}
 }
 
+impl Value {
+	// the readable form of the value, which reads back as the same value where
+	// it can be (as for anything but functions and sets)
+	pub fn to_source(&self) -> String {
+		format!("{:#}", self)
+	}
+}
+
+// written for people, with strings and chars as they are and numbers in their
+// shortest form, or in readable form (as by to_source) when alternate ({:#}),
+// with strings and chars as written in source and numbers keeping their point
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let readable = f.alternate();
+		let sequence = |f: &mut fmt::Formatter<'_>, open, items: &mut dyn Iterator<Item = &Self>, close| {
+			write!(f, "{}", open)?;
+			for (i, item) in items.enumerate() {
+				if i > 0 {
+					write!(f, " ")?;
+				}
+
+				if readable { write!(f, "{:#}", item)? } else { write!(f, "{}", item)? }
+			}
+			write!(f, "{}", close)
+		};
+
+		let leaf = match self {
+			Self::Integer(integer) => return write!(f, "{}", integer),
+			Self::Number(number) if !readable => return write!(f, "{}", number_to_string(*number)),
+			Self::Number(number) => ast::AstKind::Number(*number),
+			Self::Char(ch) if !readable => return write!(f, "{}", ch),
+			Self::Char(ch) => ast::AstKind::Char(*ch),
+			Self::String(string) if !readable => return write!(f, "{}", string),
+			Self::String(string) => ast::AstKind::String(string.clone()),
+			Self::Boolean(boolean) => return write!(f, "{}", boolean),
+			Self::List(list) if list.is_empty() => return write!(f, "nil"),
+			Self::List(list) => return sequence(f, "(", &mut list.iter(), ")"),
+			Self::Vector(vector) => return sequence(f, "[", &mut vector.iter(), "]"),
+			Self::Map(map) => return sequence(
+				f, "{", &mut map.iter().flat_map(|(key, value)| [ key, value ]), "}",
+			),
+			Self::Set(set) => return sequence(f, "#{", &mut set.iter(), "}"),
+			Self::Symbol(symbol) => return write!(f, "{}", symbol.value()),
+			Self::Keyword(symbol) => return write!(f, ":{}", symbol.value()),
+			Self::Function(function) => return write!(f, "{}", function),
+			Self::Reduced(value) if readable => return write!(f, "#<reduced {:#}>", value),
+			Self::Reduced(value) => return write!(f, "#<reduced {}>", value),
+			Self::Foreign(_) => return write!(f, "#<foreign>"),
+		};
+
+		let ast = ast::Ast { kind: leaf, location: LocationArea::default() };
+		write!(f, "{}", ast.to_inline())
+	}
+}
+
+// shortest representation which reads back as the same value, using an
+// exponent only for very large or very small magnitudes
+fn number_to_string(number: f64) -> String {
+	if number.is_nan() {
+		"nan".into()
+	} else if number.is_infinite() {
+		if number > 0f64 { "inf" } else { "-inf" }.into()
+	} else if number != 0f64 && !(1e-6..1e21).contains(&number.abs()) {
+		format!("{:e}", number)
+	} else {
+		number.to_string()
+	}
+}
+
 impl Hash for Value {
 	fn hash<H: Hasher>(&self, hasher: &mut H) {
 		std::mem::discriminant(self).hash(hasher);
 
 		match self {
+			Self::Integer(integer) => integer.hash(hasher),
+			// zero is hashed alike whatever its sign, as the two are equal
+			Self::Number(number) if *number == 0f64 => 0f64.to_bits().hash(hasher),
 			Self::Number(number) => number.to_bits().hash(hasher),
+			Self::Char(ch) => ch.hash(hasher),
 			Self::String(string) => string.hash(hasher),
 			Self::Boolean(boolean) => boolean.hash(hasher),
 			Self::List(list) => list.hash(hasher),
+			Self::Vector(vector) => vector.hash(hasher),
+			Self::Map(map) => map.hash(hasher),
+			Self::Set(set) => set.hash(hasher),
 			Self::Symbol(symbol) => symbol.hash(hasher),
+			Self::Keyword(symbol) => symbol.hash(hasher),
+			Self::Function(function) => Rc::as_ptr(function).hash(hasher),
+			Self::Reduced(value) => value.hash(hasher),
+			Self::Foreign(foreign) => foreign.hash(hasher),
 		}
 	}
 }
@@ -200,15 +371,9 @@ impl Hash for Value {
 pub struct Symbol(String);
 
 impl Symbol {
+	// names may be joined by /, as in geometry/area
 	pub fn new(src: String) -> Option<Self> {
-		if src.len() == 0 || src.chars().next().unwrap().is_ascii_digit() {
-			return None
-		}
-
-		src
-			.chars()
-			.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
-			.then(|| Self(src))
+		src.split('/').all(is_name).then_some(Self(src))
 	}
 
 	pub fn value(&self) -> &str {
@@ -221,20 +386,158 @@ impl Symbol {
 	}
 }
 
+fn is_name(src: &str) -> bool {
+	let mut chars = src.chars();
+	let start = match chars.next() {
+		// a sign may begin a symbol, unless it begins a number
+		Some('+' | '-') => !chars.clone().next()
+			.is_some_and(|ch| ch.is_ascii_digit() || ch == '.'),
+		Some(ch) => is_symbol_start(ch),
+		None => false,
+	};
+
+	start && chars.all(is_symbol_continue)
+}
+
+// identifiers follow UAX #31 (extended to any alphabetic or alphanumeric
+// characters), with a fast path for ascii which also admits some operator
+// characters; numeric characters such as superscripts or roman numerals may not
+// start an identifier (and a sign is handled separately, as it may begin a
+// number)
+fn is_symbol_start(ch: char) -> bool {
+	if ch.is_ascii() {
+		ch.is_ascii_alphabetic() || matches!(ch, '_' | '?' | '!' | '*' | '<' | '>' | '=')
+	} else {
+		(ch.is_alphabetic() || unicode_ident::is_xid_start(ch)) && !ch.is_numeric()
+	}
+}
+
+fn is_symbol_continue(ch: char) -> bool {
+	if ch.is_ascii() {
+		ch.is_ascii_alphanumeric() || matches!(ch, '_' | '-' | '?' | '!' | '*' | '<' | '>' | '=')
+	} else {
+		ch.is_alphanumeric() || unicode_ident::is_xid_continue(ch)
+	}
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct Error {
 	pub kind: ErrorKind,
 	pub location: Option<LocationArea>,
+	// the file (or other label) of the source the error arose from
+	pub source: Option<Rc<str>>,
 	pub message: String,
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+impl Error {
+	// names the source of the error, unless it already has one
+	pub fn with_source(mut self, source: impl Into<Rc<str>>) -> Self {
+		if self.source.is_none() {
+			self.source = Some(source.into());
+		}
+
+		self
+	}
+}
+
+// rendered as source:line:column: kind: message, omitting what is unknown
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if let Some(source) = &self.source {
+			write!(f, "{}:", source)?;
+		}
+
+		if let Some(location) = &self.location {
+			write!(f, "{}:{}:", location.start.line, location.start.column)?;
+		}
+
+		if self.source.is_some() || self.location.is_some() {
+			write!(f, " ")?;
+		}
+
+		match self.kind {
+			ErrorKind::UserError(_) => write!(f, "UserError: {}", self.message),
+			_ => write!(f, "{:?}: {}", self.kind, self.message),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub enum ErrorKind {
 	SyntaxError,
+	// a syntax error which the lexer skipped past to continue
+	RecoveredSyntaxError,
+	// a syntax error caused by the input ending early
+	IncompleteError,
+	// a token directly followed by something other than whitespace or a closer
+	DelimiterError,
 	NameError,
 	ArgumentError,
 	TypeError,
 	IoError,
+	// a bound set by the limits of the environment was reached
+	ResourceLimit,
+	// a value thrown by a program
+	UserError(Thrown),
+}
+
+// a thrown value, which as part of an error is only equal to itself
+#[derive(Clone)]
+pub struct Thrown(pub Rc<Value>);
+
+impl PartialEq for Thrown {
+	fn eq(&self, other: &Self) -> bool {
+		Rc::ptr_eq(&self.0, &other.0)
+	}
+}
+
+impl Eq for Thrown {}
+
+impl PartialOrd for Thrown {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Thrown {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		Rc::as_ptr(&self.0).cmp(&Rc::as_ptr(&other.0))
+	}
+}
+
+impl Hash for Thrown {
+	fn hash<H: Hasher>(&self, hasher: &mut H) {
+		Rc::as_ptr(&self.0).hash(hasher);
+	}
+}
+
+impl fmt::Debug for Thrown {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.0.fmt(f)
+	}
+}
+
+// a value of the host (constructed by Value::foreign), which is only equal to
+// itself
+#[derive(Clone)]
+pub struct Foreign(pub Rc<dyn Any>);
+
+impl PartialEq for Foreign {
+	fn eq(&self, other: &Self) -> bool {
+		Rc::ptr_eq(&self.0, &other.0)
+	}
+}
+
+impl Hash for Foreign {
+	fn hash<H: Hasher>(&self, hasher: &mut H) {
+		Rc::as_ptr(&self.0).cast::<()>().hash(hasher);
+	}
+}
+
+impl fmt::Debug for Foreign {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Foreign({:p})", Rc::as_ptr(&self.0))
+	}
 }
 
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
@@ -243,15 +546,148 @@ pub struct LocationArea {
 	pub end: LocationPoint,
 }
 
+impl LocationArea {
+	// the bytes covered within the source the locations were taken from,
+	// including all of the last character
+	pub fn byte_range(&self, src: &str) -> Range<usize> {
+		let last = src[self.end.byte..].chars().next().map_or(0, char::len_utf8);
+		self.start.byte..self.end.byte + last
+	}
+}
+
 impl From<LocationPoint> for LocationArea {
 	fn from(point: LocationPoint) -> Self {
 		Self { start: point, end: point }
 	}
 }
 
-#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+// index counts characters and byte counts bytes (both 0-based), whilst line and
+// column are 1-based as in most editors
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub struct LocationPoint {
 	pub index: usize,
+	pub byte: usize,
 	pub line: usize,
 	pub column: usize,
 }
+
+impl LocationPoint {
+	// the 1-based column in utf-16 code units, as used by many editors
+	pub fn utf16_column(&self, src: &str) -> usize {
+		let line = &src[..self.byte];
+		let start = line.rfind(['\n', '\r']).map_or(0, |i| i + 1);
+
+		line[start..].encode_utf16().count() + 1
+	}
+}
+
+impl Default for LocationPoint {
+	fn default() -> Self {
+		Self { index: 0, byte: 0, line: 1, column: 1 }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ eval, format, Value, Symbol, Error, ErrorKind };
+	use super::runtime::Environment;
+
+	use std::cell::RefCell;
+	use std::rc::Rc;
+
+	#[test]
+	fn foreign() {
+		type Counter = Rc<RefCell<i32>>;
+		let counter: Counter = Rc::new(RefCell::new(0));
+
+		let mut env = Environment {
+			symbols: maplit::hashmap! {
+				Symbol::new("counter".into()).unwrap() => Value::foreign(counter.clone()),
+			},
+			..Default::default()
+		};
+		env.register_fn("increment", |args| {
+			let counter = match args {
+				[ counter ] => counter.downcast::<Counter>(),
+				_ => None,
+			};
+
+			let counter = counter.ok_or_else(|| Error {
+				kind: ErrorKind::TypeError,
+				location: None,
+				source: None,
+				message: "increment expects a counter".into(),
+			})?;
+
+			*counter.borrow_mut() += 1;
+			Ok(Value::Integer(*counter.borrow() as i64))
+		});
+
+		// the program is given the counter itself, rather than a copy
+		let src = "(increment counter) (define same counter) (increment same)";
+		assert_eq!(eval(src, Some(&mut env)).unwrap(), Value::Integer(2));
+		assert_eq!(*counter.borrow(), 2);
+
+		// which is only equal to itself
+		assert_eq!(eval("(eq counter same)", Some(&mut env)).unwrap(), Value::Boolean(true));
+		assert_eq!(eval("(increment 1)", Some(&mut env)).unwrap_err().kind, ErrorKind::TypeError);
+	}
+
+	#[test]
+	fn builtin_names() {
+		let printed = Rc::new(RefCell::new(Vec::new()));
+
+		let mut env = Environment::default();
+		let captured = printed.clone();
+		env.register_fn("print", move |args| {
+			captured.borrow_mut().extend(args.iter().cloned());
+			Ok(Value::nil())
+		});
+
+		// a function registered under the name of one provided is kept
+		eval("(print 1)", Some(&mut env)).unwrap();
+		assert_eq!(*printed.borrow(), [ Value::Integer(1) ]);
+
+		// as is one defined by an earlier evaluation
+		eval("(def count (xs) :mine)", Some(&mut env)).unwrap();
+		assert_eq!(eval("(count [1 2])", Some(&mut env)).unwrap().to_string(), ":mine");
+
+		// and those provided stay the same function from one evaluation to the next
+		eval("(define first car)", Some(&mut env)).unwrap();
+		assert_eq!(eval("(= first car)", Some(&mut env)).unwrap(), Value::Boolean(true));
+	}
+
+	#[test]
+	fn format_is_idempotent() {
+		let src = "
+			; a greeting
+			(def greet (name)   ; who
+			  (print \"hi {name}\"))
+
+
+			(greet \"x\") /* block */ [1 2
+			3]
+		";
+
+		let once = format(src).unwrap();
+		assert_eq!(once, format(&once).unwrap());
+
+		// with every comment kept
+		for comment in [ "; a greeting", "; who", "/* block */" ] {
+			assert!(once.contains(comment), "{:?} lost from {:?}", comment, once);
+		}
+	}
+
+	#[test]
+	fn numbers_round_trip() {
+		for src in [ "0.1", "0.3", "-2.75", "1.5e300" ] {
+			let value = eval(src, None).unwrap();
+			assert_eq!(value, Value::Number(src.parse().unwrap()));
+			assert_eq!(eval(&value.to_source(), None).unwrap(), value);
+		}
+
+		assert_eq!(eval("0.1", None).unwrap().to_string(), "0.1");
+		// arithmetic is done in double precision too
+		assert_eq!(eval("(add 0.1 0.2)", None).unwrap(), Value::Number(0.1 + 0.2));
+	}
+}
@@ -0,0 +1,155 @@
+use super::{ Value, Symbol, Error, ErrorKind };
+use super::{ lexer, parser, stdlib };
+use super::runtime::{ Function, Environment, Scope };
+
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use std::rc::Rc;
+
+// the definitions a module gives those which import it, by name
+pub type Exports = Rc<Vec<(Symbol, Value)>>;
+
+// the modules imported by a program, shared by every environment within it
+#[derive(Default)]
+pub struct Modules {
+	// the exports of each module evaluated, by its canonical path
+	loaded: HashMap<PathBuf, Exports>,
+	// the modules being evaluated, each imported by the one before, along with
+	// the names each has exported so far (if it has an export list)
+	loading: Vec<(PathBuf, Option<Vec<Symbol>>)>,
+}
+
+// the exports of the module at a path, relative to the file of the environment
+// (or the working directory), with .fax added if it has no extension; a module
+// is only evaluated the first time it is imported
+pub fn import(path: &str, env: &Environment) -> Result<Exports, Error> {
+	// reading a module is reading a file, which the limits may not allow
+	if !env.limits.allows_io() {
+		return Err(Error {
+			kind: ErrorKind::ResourceLimit,
+			location: None, // todo
+			source: None,
+			message: format!("cannot import {}: input and output are not allowed", path),
+		})
+	}
+
+	let mut path = match &env.file {
+		Some(file) => file.parent().unwrap_or(Path::new("")).join(path),
+		None => PathBuf::from(path),
+	};
+	if path.extension().is_none() {
+		path.set_extension("fax");
+	}
+
+	let io_error = |error: std::io::Error| Error {
+		kind: ErrorKind::IoError,
+		location: None, // todo
+		source: None,
+		message: format!("cannot import {}: {}", path.display(), error),
+	};
+	let canonical = path.canonicalize().map_err(io_error)?;
+
+	{
+		let modules = env.modules.borrow();
+		if let Some(exports) = modules.loaded.get(&canonical) {
+			return Ok(exports.clone())
+		}
+
+		// a module imported (however indirectly) by itself is named along with
+		// each import leading back to it
+		if let Some(start) = modules.loading.iter().position(|(loading, _)| *loading == canonical) {
+			let cycle = modules.loading[start..]
+				.iter()
+				.map(|(loading, _)| loading.display().to_string())
+				.chain(std::iter::once(canonical.display().to_string()))
+				.collect::<Vec<_>>();
+
+			return Err(Error {
+				kind: ErrorKind::NameError,
+				location: None, // todo
+				source: None,
+				message: format!("circular import: {}", cycle.join(" -> ")),
+			})
+		}
+	}
+
+	let src = std::fs::read_to_string(&path).map_err(io_error)?;
+
+	env.modules.borrow_mut().loading.push((canonical.clone(), None));
+	let result = evaluate(&src, &path, env);
+	let (_, exported) = env.modules.borrow_mut().loading.pop().unwrap();
+
+	// errors within the module are reported as from its file
+	let scope = result.map_err(|error| error.with_source(path.display().to_string()))?;
+
+	let exports = match exported {
+		Some(names) => names
+			.into_iter()
+			.map(|name| match scope.get(&name) {
+				Some(value) => Ok((name, value)),
+				None => Err(Error {
+					kind: ErrorKind::NameError,
+					location: None, // todo
+					source: Some(path.display().to_string().into()),
+					message: format!("module exports undefined name {:?}", name.value()),
+				}),
+			})
+			.collect::<Result<Vec<_>, _>>()?,
+		None => scope.variables(),
+	};
+
+	let exports = Rc::new(exports);
+	env.modules.borrow_mut().loaded.insert(canonical, exports.clone());
+
+	Ok(exports)
+}
+
+// evaluates a module in its own scope, giving that scope with the functions it
+// defined bound there too, so that they can still call each other once imported
+fn evaluate(src: &str, path: &Path, env: &Environment) -> Result<Rc<Scope>, Error> {
+	let mut module_env = Environment {
+		symbols: HashMap::new(),
+		locals: Rc::new(Scope::default()),
+		// functions of the host are available to modules too
+		functions: env.functions
+			.iter()
+			.filter(|&(_, function)| matches!(**function, Function::Host(_)))
+			.map(|(name, function)| (name.clone(), function.clone()))
+			.chain(stdlib::index_within(&env.limits))
+			.collect(),
+		file: Some(path.into()),
+		modules: env.modules.clone(),
+		readers: env.readers.clone(),
+		macro_locations: HashMap::new(),
+		limits: env.limits.clone(),
+	};
+
+	let tokens = lexer::tokenize(src)?;
+	let program = parser::Parser::new(&mut tokens.into_iter().map(Ok)).parse_program()?;
+	super::run_program(&program, &mut module_env)?;
+
+	for (name, function) in module_env.functions {
+		if let Function::Native { .. } | Function::Macro { .. } = *function {
+			module_env.locals.define(name, Value::Function(function));
+		}
+	}
+
+	Ok(module_env.locals)
+}
+
+// adds names to the export list of the module being evaluated
+pub fn export(names: Vec<Symbol>, env: &Environment) -> Result<(), Error> {
+	let mut modules = env.modules.borrow_mut();
+	match modules.loading.last_mut() {
+		Some((_, exported)) => {
+			exported.get_or_insert_with(Vec::new).extend(names);
+			Ok(())
+		},
+		None => Err(Error {
+			kind: ErrorKind::SyntaxError,
+			location: None, // todo
+			source: None,
+			message: "export outside of an imported module".into(),
+		}),
+	}
+}
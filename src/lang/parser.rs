@@ -1,59 +1,535 @@
-use super::{ Value, Error, ErrorKind };
-use super::lexer::{ Token, TokenKind };
+use super::{ Value, Symbol, Error, ErrorKind, LocationArea };
+use super::ast::{ Ast, AstKind };
+use super::lexer::{ self, Token, TokenKind, StringPart };
 
-use std::iter::Peekable;
+use std::collections::HashMap;
+use std::rc::Rc;
 
-pub fn parse(
-	src: &mut impl Iterator<Item = Token>,
+// reads the form after a tag (given the contents of a string, or the source of
+// any other form, and its location) as a value
+pub type Reader = fn(&str, LocationArea) -> Result<Value, Error>;
+
+// the readers registered by the host, by tag
+pub type Readers = HashMap<Symbol, Reader>;
+
+pub fn parse<'src>(
+	src: &mut impl Iterator<Item = Token<'src>>,
 ) -> Result<Vec<Value>, Error> {
-	parse_impl(&mut src.peekable())
+	parse_with_readers(src, &Readers::new())
 }
 
-fn parse_impl(
-	src: &mut Peekable<&mut impl Iterator<Item = Token>>,
+// parses as with parse, reading tagged forms with the given readers as well as
+// those provided
+pub fn parse_with_readers<'src>(
+	src: &mut impl Iterator<Item = Token<'src>>,
+	readers: &Readers,
 ) -> Result<Vec<Value>, Error> {
-	let mut values = Vec::new();
+	Parser::new(&mut src.map(Ok))
+		.parse_program()?
+		.iter()
+		.map(|ast| ast.to_value_with(readers))
+		.collect()
+}
+
+// the reader for a tag which is always available: #b64"..." reads base64 as a
+// vector of its bytes
+pub fn provided_reader(tag: &str) -> Option<Reader> {
+	match tag {
+		"b64" => Some(read_base64),
+		_ => None,
+	}
+}
+
+fn read_base64(text: &str, location: LocationArea) -> Result<Value, Error> {
+	let invalid = |message: &str| Error {
+		kind: ErrorKind::SyntaxError,
+		location: Some(location),
+		source: None,
+		message: format!("{} in base64", message),
+	};
+
+	let text = text.trim_end_matches('=');
+	let mut bytes = Vec::new();
+	let (mut bits, mut count) = (0u32, 0);
+	for ch in text.chars() {
+		let digit = match ch {
+			'A'..='Z' => ch as u32 - 'A' as u32,
+			'a'..='z' => ch as u32 - 'a' as u32 + 26,
+			'0'..='9' => ch as u32 - '0' as u32 + 52,
+			'+' => 62,
+			'/' => 63,
+			_ => return Err(invalid(&format!("unexpected {:?}", ch))),
+		};
 
-	loop {
-		let next = src.peek();
-		if next.is_none() || next.unwrap().kind == TokenKind::RightParen {
-			break
+		bits = bits << 6 | digit;
+		count += 6;
+		if count >= 8 {
+			count -= 8;
+			bytes.push(Value::Integer((bits >> count & 0xff) as i64));
 		}
+	}
 
-		let token = src.next().unwrap();
-		values.push(match &token.kind {
-			TokenKind::LeftParen => {
-				let list = parse_impl(src)?;
-				assert_eq!(src.next().unwrap().kind, TokenKind::RightParen);
-				Value::List(list)
-			},
+	// a single digit left over cannot hold a whole byte
+	if text.len() % 4 == 1 {
+		return Err(invalid("incomplete byte"))
+	}
+
+	Ok(Value::Vector(bytes))
+}
+
+// parses a whole source, or gives none if more input could complete it
+pub fn parse_incremental(src: &str) -> Result<Option<Vec<Value>>, Error> {
+	let result = lexer::tokenize(src)
+		.and_then(|tokens| parse(&mut tokens.into_iter()));
+
+	match result {
+		Ok(values) => Ok(Some(values)),
+		Err(error) if error.kind == ErrorKind::IncompleteError => Ok(None),
+		Err(error) => Err(error),
+	}
+}
+
+// builds expressions from tokens (or the errors lexing them), ignoring comments
+pub struct Parser<'a, 'src, I: Iterator<Item = Result<Token<'src>, Error>>> {
+	src: &'a mut I,
+	peeked: Option<Token<'src>>,
+	options: ParserOptions,
+	// the delimiters and quotes around the expression being parsed
+	depth: usize,
+	// the delimiters taken from the input and not yet closed, which can differ
+	// from depth whilst recovering from an error
+	open: usize,
+	// the errors recovered from, when lenient
+	errors: Vec<Error>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ParserOptions {
+	// the most delimiters and quotes an expression may be nested within, as
+	// parsing recurses into each
+	pub max_depth: usize,
+	// continue past errors (collecting them) by skipping the rest of the
+	// expression they were found in
+	pub lenient: bool,
+}
+
+impl Default for ParserOptions {
+	fn default() -> Self {
+		Self { max_depth: 512, lenient: false }
+	}
+}
+
+impl<'a, 'src, I: Iterator<Item = Result<Token<'src>, Error>>> Parser<'a, 'src, I> {
+	pub fn new(src: &'a mut I) -> Self {
+		Self {
+			src,
+			peeked: None,
+			options: ParserOptions::default(),
+			depth: 0,
+			open: 0,
+			errors: Vec::new(),
+		}
+	}
+
+	pub fn with_options(mut self, options: ParserOptions) -> Self {
+		self.options = options;
+		self
+	}
 
-			TokenKind::RightParen => unreachable!(),
+	pub fn lenient(mut self) -> Self {
+		self.options.lenient = true;
+		self
+	}
+
+	// the errors recovered from so far (in the order they were found)
+	pub fn take_errors(&mut self) -> Vec<Error> {
+		std::mem::take(&mut self.errors)
+	}
+
+	// parses every expression up to the end of the input
+	pub fn parse_program(&mut self) -> Result<Vec<Ast>, Error> {
+		let mut program = Vec::new();
+		loop {
+			match self.parse_expr() {
+				Ok(Some(ast)) => program.push(ast),
+				Ok(None) => break,
+				Err(error) => self.recover(error, 0)?,
+			}
+		}
+
+		Ok(program)
+	}
+
+	// parses the next expression, or gives none at the end of the input
+	pub fn parse_expr(&mut self) -> Result<Option<Ast>, Error> {
+		match self.next()? {
+			Some(token) => self.parse_token(token).map(Some),
+			None => Ok(None),
+		}
+	}
+
+	fn peek(&mut self) -> Result<Option<&Token<'src>>, Error> {
+		while self.peeked.is_none() {
+			match self.src.next() {
+				Some(Ok(Token { kind: TokenKind::Comment { .. } | TokenKind::Eof, .. })) => (),
+				Some(Ok(token)) => self.peeked = Some(token),
+				// errors from the input are skipped past like any other
+				Some(Err(error)) => self.recover(error, self.open)?,
+				None => break,
+			}
+		}
+
+		Ok(self.peeked.as_ref())
+	}
+
+	fn next(&mut self) -> Result<Option<Token<'src>>, Error> {
+		self.peek()?;
 
-			TokenKind::LeftBracket | TokenKind::RightBracket => return Err(Error {
+		let token = self.peeked.take();
+		if let Some(token) = &token {
+			if is_opener(&token.kind) {
+				self.open += 1;
+			} else if is_closer(&token.kind) {
+				self.open = self.open.saturating_sub(1);
+			}
+		}
+
+		Ok(token)
+	}
+
+	// gives the error back unless lenient, in which case it is kept and the
+	// input skipped until only the given number of delimiters are left open,
+	// so that parsing can continue from there
+	fn recover(&mut self, mut error: Error, open: usize) -> Result<(), Error> {
+		if !self.options.lenient {
+			return Err(error)
+		}
+
+		if error.kind == ErrorKind::SyntaxError {
+			error.kind = ErrorKind::RecoveredSyntaxError;
+		}
+		self.errors.push(error);
+
+		while self.open > open {
+			if self.next()?.is_none() {
+				break
+			}
+		}
+
+		Ok(())
+	}
+
+	// expressions within others are parsed apart from the rest (in frames kept
+	// small), so that as many fit on the stack as the depth allows
+	fn parse_token(&mut self, token: Token<'src>) -> Result<Ast, Error> {
+		let nests = matches!(
+			token.kind,
+			TokenKind::LeftParen |
+			TokenKind::LeftBracket |
+			TokenKind::LeftBrace |
+			TokenKind::Quote |
+			TokenKind::Quasiquote |
+			TokenKind::Unquote |
+			TokenKind::UnquoteSplice |
+			TokenKind::Splat |
+			TokenKind::Label(_) |
+			TokenKind::Tag(_)
+		);
+
+		if !nests {
+			return self.parse_form(token)
+		}
+
+		if self.depth == self.options.max_depth {
+			return Err(Error {
 				kind: ErrorKind::SyntaxError,
 				location: Some(token.location),
-				message: "unexpected unimplemented bracket".into(),
-			}),
-			TokenKind::LeftBrace | TokenKind::RightBrace => return Err(Error {
+				source: None,
+				message: "expression nested too deeply".into(),
+			})
+		}
+
+		self.depth += 1;
+		let result = match token.kind {
+			TokenKind::LeftParen => self.parse_sequence(token, TokenKind::RightParen, AstKind::List),
+			TokenKind::LeftBracket => self.parse_sequence(token, TokenKind::RightBracket, AstKind::Vector),
+			TokenKind::LeftBrace => self.parse_sequence(token, TokenKind::RightBrace, AstKind::Map),
+			_ => self.parse_quoted(token),
+		};
+		self.depth -= 1;
+
+		result
+	}
+
+	// 'x, `x, ,x, and ,@x are read as (quote x), (quasiquote x), etc, ..x as
+	// (splat x), name: x as (named-arg name x), and #tag x as (tagged tag x)
+	fn parse_quoted(&mut self, token: Token<'src>) -> Result<Ast, Error> {
+		match self.peek()? {
+			Some(next) if !is_closer(&next.kind) => {
+				let next = self.next()?.unwrap();
+				let quoted = self.parse_token(next)?;
+				Ok(quoted_form(token, quoted))
+			},
+			next => Err(missing_quoted(&token, next.is_none())),
+		}
+	}
+
+	fn parse_form(&mut self, token: Token<'src>) -> Result<Ast, Error> {
+		let kind = match token.kind {
+			TokenKind::RightParen |
+			TokenKind::RightBracket |
+			TokenKind::RightBrace => return Err(Error {
 				kind: ErrorKind::SyntaxError,
 				location: Some(token.location),
-				message: "unexpected unimplemented brace".into(),
+				source: None,
+				message: format!("unexpected '{}'", token.kind),
 			}),
 			TokenKind::Dot => return Err(Error {
 				kind: ErrorKind::SyntaxError,
 				location: Some(token.location),
-				message: "unexpected unimplemented path delimeter".into(),
+				source: None,
+				message: "unexpected unimplemented path delimiter".into(),
 			}),
 
-			TokenKind::Symbol(symbol) => Value::Symbol(symbol.clone()),
+			TokenKind::Symbol(symbol) => AstKind::Symbol(Symbol(symbol.into_owned())),
+			TokenKind::Keyword(symbol) => AstKind::Keyword(Symbol(symbol.into_owned())),
+
+			TokenKind::Integer(value) => AstKind::Integer(value),
+			TokenKind::Number(value) => AstKind::Number(value),
+			TokenKind::Char(value) => AstKind::Char(value),
+			TokenKind::String(value) => AstKind::String(value.into_owned()),
+
+			// "a {b} c" is read as (cat "a " b " c")
+			TokenKind::Interpolation(parts) => {
+				let mut list = vec![ Ast {
+					kind: AstKind::Symbol(Symbol("cat".into())),
+					location: token.location,
+				} ];
+
+				for part in parts {
+					list.push(match part {
+						StringPart::Literal(string) => Ast {
+							kind: AstKind::String(string),
+							location: token.location,
+						},
+						StringPart::Expression(tokens) => {
+							let mut tokens = tokens.into_iter().map(Ok);
+							let mut parser = Parser::new(&mut tokens).with_options(ParserOptions {
+								lenient: false,
+								..self.options
+							});
+							parser.depth = self.depth;
+
+							let mut expressions = parser.parse_program()?;
+							if expressions.len() != 1 {
+								return Err(Error {
+									kind: ErrorKind::SyntaxError,
+									location: Some(token.location),
+									source: None,
+									message: "expected one expression in string interpolation".into(),
+								})
+							}
 
-			TokenKind::Number(value) => Value::Number(*value),
-			TokenKind::String(value) => Value::String(value.clone()),
-			TokenKind::Boolean(value) => Value::Boolean(*value),
-			TokenKind::Nil => Value::List(Vec::new()),
+							expressions.remove(0)
+						},
+					});
+				}
+
+				AstKind::List(list.into())
+			},
+			TokenKind::Boolean(value) => AstKind::Boolean(value),
+			TokenKind::Nil => AstKind::Nil,
+
+			// those nesting others are parsed by parse_token
+			TokenKind::LeftParen |
+			TokenKind::LeftBracket |
+			TokenKind::LeftBrace |
+			TokenKind::Quote |
+			TokenKind::Quasiquote |
+			TokenKind::Unquote |
+			TokenKind::UnquoteSplice |
+			TokenKind::Splat |
+			TokenKind::Label(_) |
+			TokenKind::Tag(_) |
+			TokenKind::Comment { .. } |
+			TokenKind::Eof => unreachable!(),
+		};
+
+		Ok(Ast { kind, location: token.location })
+	}
+
+	// parses the items of a list, vector, or map up to its closing delimiter
+	fn parse_sequence(
+		&mut self,
+		open: Token<'src>,
+		close: TokenKind<'src>,
+		kind: fn(Rc<[Ast]>) -> AstKind,
+	) -> Result<Ast, Error> {
+		let mut items = Vec::new();
+		let level = self.open;
+
+		loop {
+			match self.next()? {
+				Some(token) if !is_closer(&token.kind) => match self.parse_token(token) {
+					Ok(item) => items.push(item),
+					Err(error) => self.recover(error, level)?,
+				},
+				end => return self.end_sequence(open, close, kind, items, end, level),
+			}
+		}
+	}
+
+	// ends a sequence at a closing delimiter (or the end of the input), apart
+	// from parse_sequence so that its frame stays small as it recurses
+	fn end_sequence(
+		&mut self,
+		open: Token<'src>,
+		close: TokenKind<'src>,
+		kind: fn(Rc<[Ast]>) -> AstKind,
+		items: Vec<Ast>,
+		end: Option<Token<'src>>,
+		level: usize,
+	) -> Result<Ast, Error> {
+		let token = match end {
+			Some(token) => token,
+			None => {
+				self.recover(Error {
+					kind: ErrorKind::IncompleteError,
+					location: Some(open.location),
+					source: None,
+					message: format!("unclosed '{}'", open.kind),
+				}, level)?;
+
+				// when lenient, the sequence is taken to end with the input
+				let end = items.last().map_or(open.location.end, |item: &Ast| item.location.end);
+				return Ok(Ast {
+					kind: kind(items.into()),
+					location: LocationArea { start: open.location.start, end },
+				})
+			},
+		};
+
+		if token.kind == close {
+			// each key of a map must be followed by its value
+			if close == TokenKind::RightBrace && !items.len().is_multiple_of(2) {
+				self.recover(Error {
+					kind: ErrorKind::SyntaxError,
+					location: items.last().map(|key: &Ast| key.location),
+					source: None,
+					message: "key has no value in map literal".into(),
+				}, self.open)?;
+			}
+		} else {
+			// when lenient, the wrong delimiter is taken as the right one
+			self.recover(Error {
+				kind: ErrorKind::SyntaxError,
+				location: Some(token.location),
+				source: None,
+				message: format!(
+					"mismatched '{}' (expected '{}' to close '{}')",
+					token.kind, close, open.kind,
+				),
+			}, level)?;
+		}
+
+		Ok(Ast {
+			kind: kind(items.into()),
+			location: LocationArea {
+				start: open.location.start,
+				end: token.location.end,
+			},
+		})
+	}
+}
+
+fn quote_name(kind: &TokenKind) -> &'static str {
+	match kind {
+		TokenKind::Quote => "quote",
+		TokenKind::Quasiquote => "quasiquote",
+		TokenKind::Unquote => "unquote",
+		TokenKind::UnquoteSplice => "unquote-splicing",
+		TokenKind::Splat => "splat",
+		TokenKind::Label(_) => "named-arg",
+		TokenKind::Tag(_) => "tagged",
+		_ => unreachable!(),
+	}
+}
+
+fn missing_quoted(token: &Token, at_end: bool) -> Error {
+	Error {
+		kind: if at_end { ErrorKind::IncompleteError } else { ErrorKind::SyntaxError },
+		location: Some(token.location),
+		source: None,
+		message: format!("expected expression after {}", quote_name(&token.kind)),
+	}
+}
+
+// the list a quote (or the like) is read as, given the form after it
+fn quoted_form(token: Token, quoted: Ast) -> Ast {
+	let mut items = vec![ Ast {
+		kind: AstKind::Symbol(Symbol(quote_name(&token.kind).into())),
+		location: token.location,
+	} ];
+	if let TokenKind::Label(label) | TokenKind::Tag(label) = token.kind {
+		items.push(Ast {
+			kind: AstKind::Symbol(Symbol(label.into_owned())),
+			location: token.location,
 		});
 	}
+	items.push(quoted);
+
+	Ast {
+		location: LocationArea {
+			start: token.location.start,
+			end: items[items.len() - 1].location.end,
+		},
+		kind: AstKind::List(items.into()),
+	}
+}
+
+fn is_opener(kind: &TokenKind) -> bool {
+	matches!(kind, TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace)
+}
+
+fn is_closer(kind: &TokenKind) -> bool {
+	matches!(kind, TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ Parser, ParserOptions };
+	use crate::{ Error, ErrorKind };
+	use crate::lexer::Lexer;
+
+	fn parse(src: &str, options: ParserOptions) -> Result<usize, Error> {
+		let mut tokens = Lexer::from_str(src);
+		Parser::new(&mut tokens).with_options(options).parse_program().map(|program| program.len())
+	}
+
+	#[test]
+	fn too_deep() {
+		let src = "(".repeat(50_000);
+		let error = parse(&src, ParserOptions::default()).unwrap_err();
+		assert_eq!(error.kind, ErrorKind::SyntaxError);
+		assert_eq!(error.message, "expression nested too deeply");
+		// located at the first delimiter past the limit
+		assert_eq!(error.location.unwrap().start.index, 512);
+		assert_eq!(crate::eval(&src, None).unwrap_err().kind, ErrorKind::SyntaxError);
+
+		// quotes are nested within as well
+		let src = format!("{}x", "'".repeat(50_000));
+		assert_eq!(parse(&src, ParserOptions::default()).unwrap_err().kind, ErrorKind::SyntaxError);
 
-	Ok(values)
+		let options = ParserOptions { max_depth: 3, ..Default::default() };
+		assert_eq!(parse("[[[1]]]", options).unwrap(), 1);
+		assert_eq!(parse("[[[[1]]]]", options).unwrap_err().location.unwrap().start.index, 3);
+	}
+
+	#[test]
+	fn too_deep_lenient() {
+		let src = format!("{}{} 1", "(".repeat(1000), ")".repeat(1000));
+		let (_, errors) = crate::parse_all(&src);
+		assert_eq!(errors.len(), 1);
+		assert_eq!(errors[0].message, "expression nested too deeply");
+	}
 }
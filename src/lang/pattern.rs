@@ -0,0 +1,226 @@
+use super::{ Value, Symbol, Error, ErrorKind, LocationArea };
+use super::ast::{ Ast, AstKind };
+use super::runtime::{ Scope, splat };
+
+use std::fmt;
+
+// the shape of a value taken apart by a binding, as in the arguments of a
+// function or the bindings of a let
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+	// binds the whole value
+	Symbol(Symbol),
+	// _, which matches anything and binds nothing
+	Ignore,
+	// a number, string, char, boolean, keyword, or quoted symbol, which matches
+	// only an equal value
+	Literal(Value, Option<LocationArea>),
+	// a list or vector of patterns matching the items of one, in order, and
+	// optionally a pattern after .. matching the rest
+	Sequence {
+		items: Vec<Pattern>,
+		rest: Option<Box<Pattern>>,
+		is_vector: bool,
+		location: Option<LocationArea>,
+	},
+}
+
+impl Pattern {
+	// the pattern written by a value, within a form at the given location (if it
+	// was read from source), which is where errors in parsing or binding it are
+	// reported unless it is a list with a location of its own
+	pub fn parse(value: &Value, location: Option<LocationArea>) -> Result<Self, Error> {
+		let location = match value {
+			Value::List(list) => list.location().or(location),
+			_ => location,
+		};
+
+		let (mut values, is_vector) = match value {
+			Value::Symbol(symbol) => return match symbol.value() {
+				"_" => Ok(Self::Ignore),
+				".." => Err(Error {
+					kind: ErrorKind::ArgumentError,
+					location,
+					source: None,
+					message: "'..' outside of a list or vector pattern".into(),
+				}),
+				_ => Ok(Self::Symbol(symbol.clone())),
+			},
+			Value::Integer(_) |
+			Value::Number(_) |
+			Value::Char(_) |
+			Value::String(_) |
+			Value::Boolean(_) |
+			Value::Keyword(_) => return Ok(Self::Literal(value.clone(), location)),
+			Value::List(list) => match list.iter().collect::<Vec<_>>()[..] {
+				[ Value::Symbol(Symbol(quote)), quoted @ Value::Symbol(_) ] if quote == "quote" =>
+					return Ok(Self::Literal(quoted.clone(), location)),
+				_ => (list.iter().cloned().collect(), false),
+			},
+			Value::Vector(vector) => (vector.clone(), true),
+			other => return Err(Error {
+				kind: ErrorKind::ArgumentError,
+				location,
+				source: None,
+				message: format!("cannot bind to {}", other.type_name()),
+			}),
+		};
+
+		// .. may only come before the last pattern, though the last may also be
+		// written directly after it, as ..rest
+		if let Some(rest) = values.last().and_then(splat).cloned() {
+			values.pop();
+			values.push(Value::Symbol(Symbol("..".into())));
+			values.push(rest);
+		}
+
+		let split = values.iter().position(|value| matches!(
+			value,
+			Value::Symbol(symbol) if symbol.value() == "..",
+		));
+
+		let (items, rest) = match split {
+			Some(split) if split + 2 == values.len() =>
+				(&values[..split], Some(Box::new(Self::parse(&values[split + 1], location)?))),
+			Some(_) => return Err(Error {
+				kind: ErrorKind::ArgumentError,
+				location,
+				source: None,
+				message: "expected one pattern after '..'".into(),
+			}),
+			None => (&values[..], None),
+		};
+
+		Ok(Self::Sequence {
+			items: items.iter().map(|item| Self::parse(item, location)).collect::<Result<_, _>>()?,
+			rest,
+			is_vector,
+			location,
+		})
+	}
+
+	// binds the symbols of the pattern to the parts of the value they match in
+	// the given scope, or fails if the value does not fit
+	pub fn bind(&self, value: Value, scope: &Scope) -> Result<(), Error> {
+		let (patterns, rest, location) = match self {
+			Self::Symbol(symbol) => {
+				scope.define(symbol.clone(), value);
+				return Ok(())
+			},
+			Self::Ignore => return Ok(()),
+			Self::Literal(literal, _) if *literal == value => return Ok(()),
+			Self::Literal(_, location) => return Err(Error {
+				kind: ErrorKind::ArgumentError,
+				location: *location,
+				source: None,
+				message: format!("cannot match {} against {}", value.type_name(), self),
+			}),
+			Self::Sequence { items, rest, location, .. } => (items, rest, *location),
+		};
+
+		let (mut values, is_vector) = match value {
+			Value::List(list) => (list.into_vec(), false),
+			Value::Vector(vector) => (vector, true),
+			other => return Err(Error {
+				kind: ErrorKind::TypeError,
+				location,
+				source: None,
+				message: format!("cannot match {} against {}", other.type_name(), self),
+			}),
+		};
+
+		let fits = match rest {
+			Some(_) => values.len() >= patterns.len(),
+			None => values.len() == patterns.len(),
+		};
+
+		if !fits {
+			return Err(Error {
+				kind: ErrorKind::ArgumentError,
+				location,
+				source: None,
+				message: format!(
+					"cannot match {} items against {} (expected {}{})",
+					values.len(), self, if rest.is_some() { "at least " } else { "" }, patterns.len(),
+				),
+			})
+		}
+
+		let remaining = values.split_off(patterns.len());
+		for (pattern, value) in patterns.iter().zip(values) {
+			pattern.bind(value, scope)?;
+		}
+
+		// the rest is of the same kind as the value it is taken from
+		match rest {
+			Some(rest) if is_vector => rest.bind(Value::Vector(remaining), scope),
+			Some(rest) => rest.bind(Value::List(remaining.into()), scope),
+			None => Ok(()),
+		}
+	}
+}
+
+// written as in source, such as [a _ .. rest]
+impl fmt::Display for Pattern {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let (items, rest, is_vector) = match self {
+			Self::Symbol(symbol) => return write!(f, "{}", symbol.value()),
+			Self::Ignore => return write!(f, "_"),
+			Self::Literal(literal, _) => return write!(f, "{}", literal_source(literal)),
+			Self::Sequence { items, rest, is_vector, .. } => (items, rest, *is_vector),
+		};
+
+		let mut parts = items.iter().map(Self::to_string).collect::<Vec<_>>();
+		if let Some(rest) = rest {
+			parts.push("..".into());
+			parts.push(rest.to_string());
+		}
+
+		if is_vector {
+			write!(f, "[{}]", parts.join(" "))
+		} else {
+			write!(f, "({})", parts.join(" "))
+		}
+	}
+}
+
+// the source of a literal, where only a symbol needs quoting
+fn literal_source(value: &Value) -> String {
+	let kind = match value {
+		Value::Integer(integer) => AstKind::Integer(*integer),
+		Value::Number(number) => AstKind::Number(*number),
+		Value::Char(ch) => AstKind::Char(*ch),
+		Value::String(string) => AstKind::String(string.clone()),
+		Value::Boolean(boolean) => AstKind::Boolean(*boolean),
+		Value::Keyword(symbol) => AstKind::Keyword(symbol.clone()),
+		Value::Symbol(symbol) => return format!("'{}", symbol.value()),
+		_ => unreachable!(),
+	};
+
+	Ast { kind, location: Default::default() }.to_inline()
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{ eval, ErrorKind };
+
+	// the kind of error evaluating the source gives, and the line and column it
+	// is reported at
+	fn error(src: &str) -> (ErrorKind, Option<(usize, usize)>) {
+		let error = eval(src, None).unwrap_err();
+		(error.kind, error.location.map(|location| (location.start.line, location.start.column)))
+	}
+
+	#[test]
+	fn locations() {
+		// a list pattern is reported where it was written
+		assert_eq!(error("(let [(a b) '(1)] a)"), (ErrorKind::ArgumentError, Some((1, 7))));
+		assert_eq!(error("(let [(a .. b c) '(1)] a)"), (ErrorKind::ArgumentError, Some((1, 7))));
+
+		// and any other at the form binding it
+		assert_eq!(error("(def f (x)\n\t(let [[a b] x] a))\n(f [1])"), (ErrorKind::ArgumentError, Some((2, 2))));
+		assert_eq!(error("(let [[a] 1] a)"), (ErrorKind::TypeError, Some((1, 1))));
+		assert_eq!(error("(let [{} 1] nil)"), (ErrorKind::ArgumentError, Some((1, 1))));
+		assert_eq!(error("(def f (x :a) x)\n(f 1 2)"), (ErrorKind::ArgumentError, Some((1, 8))));
+	}
+}
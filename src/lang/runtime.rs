@@ -1,221 +1,1575 @@
-use super::{ Value, Symbol, Error, ErrorKind };
+use super::{ Value, Symbol, Error, ErrorKind, LocationArea, Thrown };
+use super::limits::Limits;
+use super::list::List;
+use super::module::{ self, Modules };
+use super::parser::{ Reader, Readers };
+use super::pattern::Pattern;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::sync::atomic::{ AtomicUsize, Ordering };
-
-static LAMBDA_COUNTER: AtomicUsize = AtomicUsize::new(0);
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
 
+// evaluates an expression, within the depth allowed by the limits of the
+// environment
 pub fn run(value: Value, env: &mut Environment) -> Result<Value, Error> {
-	match value {
-		Value::List(mut items) => match &items[0] {
-			Value::Symbol(symbol @ Symbol(name)) => match name.as_str() {
-				"quote" => Ok(if items.len() > 2 {
-					Value::List(items[1..].to_vec())
-				} else {
-					items[1].clone()
-				}),
-				"block" => {
-					let mut last = None;
-					for item in items[1..].to_vec() {
-						last = Some(run(item, env)?);
-					}
+	env.limits.enter()?;
+	let result = evaluate(value, env);
+	env.limits.leave();
 
-					Ok(last.unwrap_or(Value::nil()))
-				},
-				"if" => {
-					if items.len() < 3 {
-						return Err(Error {
-							kind: ErrorKind::ArgumentError,
-							location: None, // todo
-							message: "if requires at least one branch".into(),
-						})
-					}
-
-					let conditional_branches = (items.len() - 1) / 2;
-					let mut selected = None;
-					for i in 0..conditional_branches {
-						if run(std::mem::take(&mut items[i * 2 + 1]), env)?.truthy() {
-							selected = Some(i);
-							break
-						}
-					}
-
-					if let Some(i) = selected {
-						run(std::mem::take(&mut items[i * 2 + 2]), env)
-					} else {
-						if items.len() % 2 == 0 {
-							let items_len = items.len();
-							run(std::mem::take(&mut items[items_len - 1]), env)
-						} else {
-							Ok(Value::nil())
-						}
-					}
-				},
-				"and" | "all" => {
-					let mut nil = Value::nil();
-					for item in items[1..].to_vec() {
-						nil = run(item, env)?;
-						if !nil.truthy() {
-							break
-						}
-					}
-
-					Ok(nil)
-				},
-				"or" | "any" => {
-					let mut non_nil = Value::nil();
-					for item in items[1..].to_vec() {
-						non_nil = run(item, env)?;
-						if non_nil.truthy() {
-							break
-						}
-					}
-
-					Ok(non_nil)
-				},
-				"fun" | "def" => {
-					let basis = if name.as_str() == "fun" { 0 } else { 1 };
+	result
+}
 
-					let args = match &items[basis + 1] {
-						Value::List(list) => list,
-						_ => return Err(Error {
-							kind: ErrorKind::ArgumentError,
-							location: None, // todo
-							message: "expected list to define function arguments".into(),
-						}),
-					};
-
-					if
-						args
-							.iter()
-							.any(|value| match value {
-								Value::Symbol(_) => false,
-								_ => true,
-							})
-					{
-						return Err(Error {
-							kind: ErrorKind::ArgumentError,
-							location: None, // todo
-							message: "non-symbol found in argument definition".into(),
-						})
-					}
-
-					let args = args
-						.iter()
-						.map(|value| match value {
-							Value::Symbol(symbol) => symbol.clone(),
-							_ => unreachable!(),
-						})
-						.collect::<Vec<_>>();
-
-					let symbol = if name.as_str() == "fun" {
-						Symbol(format!(
-							"%{}",
-							LAMBDA_COUNTER.fetch_add(1, Ordering::SeqCst),
-						))
-					} else {
-						match &items[1] {
-							Value::Symbol(symbol) => symbol.clone(),
-							_ => return Err(Error {
+// expressions in tail position are evaluated by looping rather than recursing,
+// so that tail calls (including to other functions) take no further stack
+fn evaluate(value: Value, env: &mut Environment) -> Result<Value, Error> {
+	let mut value = value;
+	// the environment of the function most recently tail called
+	let mut frame: Option<Environment> = None;
+	// the loop whose body the value is in tail position of, if any
+	let mut target: Option<LoopTarget> = None;
+
+	loop {
+		let env = match &mut frame {
+			Some(frame) => frame,
+			None => &mut *env,
+		};
+		env.limits.step()?;
+
+		return match value {
+			// nil (the empty list) evaluates to itself
+			Value::List(items) if items.is_empty() => Ok(Value::List(items)),
+			// a call is taken apart by position, so its items are moved out of the list
+			Value::List(list) => {
+				let location = list.location();
+				let mut items = list.into_vec();
+				match &items[0] {
+					Value::Symbol(symbol @ Symbol(name)) => match name.as_str() {
+						"quote" => match items.len() {
+							1 => Err(Error {
 								kind: ErrorKind::ArgumentError,
 								location: None, // todo
-								message: "expected symbol to identify definition".into(),
+								source: None,
+								message: "quote requires an argument".into(),
 							}),
-						}
-					};
+							2 => Ok(items[1].clone()),
+							_ => Ok(Value::List(items[1..].to_vec().into())),
+						},
+						"quasiquote" => {
+							if items.len() != 2 {
+								return Err(Error {
+									kind: ErrorKind::ArgumentError,
+									location: None, // todo
+									source: None,
+									message: "quasiquote requires one argument".into(),
+								})
+							}
 
-					env.functions.insert(
-						symbol.clone(),
-						Function::Native {
-							args,
-							body: items[basis + 2..].to_vec(),
+							quasiquote(items[1].clone(), 1, env)
 						},
-					);
+						"block" => match items.pop() {
+							Some(last) if !items.is_empty() => {
+								for item in items.drain(1..) {
+									run(item, env)?;
+								}
 
-					Ok(Value::Symbol(symbol))
-				},
-				_ => match env.functions.get(&symbol) {
-					Some(Function::Native { args, body }) => {
-						let (args, body) = (args.clone(), body.clone());
+								value = last;
+								continue
+							},
+							_ => Ok(Value::nil()),
+						},
+						"if" => {
+							if items.len() < 3 {
+								return Err(Error {
+									kind: ErrorKind::ArgumentError,
+									location: None, // todo
+									source: None,
+									message: "if requires at least one branch".into(),
+								})
+							}
 
-						if items.len() - 1 != args.len() {
-							return Err(Error {
-								kind: ErrorKind::ArgumentError,
-								location: None, // todo
-								message: format!(
-									"{} arguments provided ({} expected)",
-									items.len() - 1, args.len(),
+							let conditional_branches = (items.len() - 1) / 2;
+							let mut selected = None;
+							for i in 0..conditional_branches {
+								if run(std::mem::take(&mut items[i * 2 + 1]), env)?.truthy() {
+									selected = Some(i);
+									break
+								}
+							}
+
+							if let Some(i) = selected {
+								value = std::mem::take(&mut items[i * 2 + 2]);
+								continue
+							} else {
+								if items.len() % 2 == 0 {
+									value = items.pop().unwrap();
+									continue
+								} else {
+									Ok(Value::nil())
+								}
+							}
+						},
+						"cond" => {
+							let mut selected = None;
+							for branch in items.drain(1..) {
+								let mut branch = match branch {
+									Value::List(branch) if !branch.is_empty() => branch.into_vec(),
+									_ => return Err(Error {
+										kind: ErrorKind::ArgumentError,
+										location: None, // todo
+										source: None,
+										message: "cond branches must be non-empty lists".into(),
+									}),
+								};
+
+								// else is a catch-all, like any truthy condition
+								let condition = match branch.remove(0) {
+									Value::Symbol(Symbol(name)) if name == "else" => Value::Boolean(true),
+									condition => run(condition, env)?,
+								};
+
+								if condition.truthy() {
+									selected = Some((condition, branch));
+									break
+								}
+							}
+
+							// a branch of only a condition gives the condition's value
+							match selected {
+								Some((condition, mut body)) => match body.pop() {
+									Some(last) => {
+										for item in body {
+											run(item, env)?;
+										}
+
+										value = last;
+										continue
+									},
+									None => Ok(condition),
+								},
+								None => Ok(Value::nil()),
+							}
+						},
+						"match" => {
+							if items.len() < 2 {
+								return Err(Error {
+									kind: ErrorKind::ArgumentError,
+									location: None, // todo
+									source: None,
+									message: "match requires a value to match".into(),
+								})
+							}
+
+							let subject = run(std::mem::take(&mut items[1]), env)?;
+
+							// arms are tried in order, each binding its pattern in a scope
+							// of its own, which only the arm taken is evaluated in
+							let mut selected = None;
+							for arm in items.drain(2..) {
+								let (mut arm, arm_location) = match arm {
+									Value::List(arm) if arm.len() >= 2 => {
+										let arm_location = arm.location().or(location);
+										(arm.into_vec(), arm_location)
+									},
+									_ => return Err(Error {
+										kind: ErrorKind::ArgumentError,
+										location: None, // todo
+										source: None,
+										message: "match arms must be lists of a pattern and a result".into(),
+									}),
+								};
+
+								// else is a catch-all, like _
+								let pattern = match arm.remove(0) {
+									Value::Symbol(Symbol(name)) if name == "else" => Pattern::Ignore,
+									pattern => Pattern::parse(&pattern, arm_location)?,
+								};
+
+								let scope = Scope::child(&env.locals);
+								if pattern.bind(subject.clone(), &scope).is_err() {
+									continue
+								}
+
+								let mut arm_env = Environment {
+									locals: scope,
+									..env.clone()
+								};
+
+								// a guard after when must also hold for the arm to be taken
+								if matches!(&arm[0], Value::Symbol(Symbol(name)) if name == "when") {
+									if arm.len() < 3 {
+										return Err(Error {
+											kind: ErrorKind::ArgumentError,
+											location: None, // todo
+											source: None,
+											message: "expected a condition and a result after when".into(),
+										})
+									}
+
+									if !run(std::mem::take(&mut arm[1]), &mut arm_env)?.truthy() {
+										continue
+									}
+
+									arm.drain(..2);
+								}
+
+								selected = Some((arm, arm_env));
+								break
+							}
+
+							match selected {
+								Some((arm, arm_env)) => {
+									let mut body = vec![ Value::Symbol(Symbol("block".into())) ];
+									body.extend(arm);
+
+									value = Value::List(body.into());
+									frame = Some(arm_env);
+									continue
+								},
+								None => Err(Error {
+									kind: ErrorKind::ArgumentError,
+									location,
+									source: None,
+									message: format!("no arm of match fits {}", subject.type_name()),
+								}),
+							}
+						},
+						"let" | "loop" => {
+							let form = if name.as_str() == "loop" { "loop" } else { "let" };
+
+							let bindings = match items.get_mut(1) {
+								Some(Value::Vector(bindings)) => std::mem::take(bindings),
+								Some(Value::List(bindings)) => std::mem::take(bindings).into_vec(),
+								_ => return Err(Error {
+									kind: ErrorKind::ArgumentError,
+									location: None, // todo
+									source: None,
+									message: format!("expected vector of bindings for {}", form),
+								}),
+							};
+
+							if bindings.len() % 2 != 0 {
+								return Err(Error {
+									kind: ErrorKind::ArgumentError,
+									location: None, // todo
+									source: None,
+									message: format!("symbol has no value in {} bindings", form),
+								})
+							}
+
+							// bindings are made in order, so each can refer to those
+							// before it, within a scope dropped after the body
+							let mut new_env = Environment {
+								locals: Scope::child(&env.locals),
+								..env.clone()
+							};
+							let mut patterns = Vec::new();
+							let mut bindings = bindings.into_iter();
+							while let (Some(pattern), Some(value)) = (bindings.next(), bindings.next()) {
+								let pattern = Pattern::parse(&pattern, location)?;
+								let value = run(value, &mut new_env)?;
+								pattern.bind(value, &new_env.locals)?;
+								patterns.push(pattern);
+							}
+
+							let mut body = vec![ Value::Symbol(Symbol("block".into())) ];
+							body.extend(items.drain(2..));
+
+							// a recur in tail position of the body of a loop returns to
+							// it, with its patterns bound anew
+							if form == "loop" {
+								target = Some(LoopTarget {
+									patterns,
+									body: body.clone(),
+									locals: env.locals.clone(),
+								});
+							}
+
+							value = Value::List(body.into());
+							frame = Some(new_env);
+							continue
+						},
+						"recur" => {
+							let target = match &target {
+								Some(target) => target,
+								None => return Err(Error {
+									kind: ErrorKind::SyntaxError,
+									location: None, // todo
+									source: None,
+									message: "recur outside of tail position of loop".into(),
+								}),
+							};
+
+							if items.len() - 1 != target.patterns.len() {
+								return Err(Error {
+									kind: ErrorKind::ArgumentError,
+									location: None, // todo
+									source: None,
+									message: format!(
+										"{} arguments provided to recur ({} expected)",
+										items.len() - 1, target.patterns.len(),
+									),
+								})
+							}
+
+							let values = items
+								.drain(1..)
+								.map(|item| run(item, env))
+								.collect::<Result<Vec<_>, _>>()?;
+
+							// the environment is that of the loop (or a scope within it),
+							// whose patterns are bound in a new scope each time around
+							env.locals = Scope::child(&target.locals);
+							for (pattern, value) in target.patterns.iter().zip(values) {
+								pattern.bind(value, &env.locals)?;
+							}
+
+							value = Value::List(target.body.clone().into());
+							continue
+						},
+						"try" => {
+							// the last argument is a catch clause handling any error from
+							// those before it
+							let clause = match items.pop() {
+								Some(Value::List(clause)) if items.len() > 1 => clause.into_vec(),
+								_ => Vec::new(),
+							};
+
+							let (symbol, handler) = match &clause[..] {
+								[ Value::Symbol(Symbol(head)), Value::Symbol(symbol), handler @ .. ]
+									if head == "catch" => (symbol.clone(), handler.to_vec()),
+								_ => return Err(Error {
+									kind: ErrorKind::ArgumentError,
+									location: None, // todo
+									source: None,
+									message: "try requires a body followed by (catch symbol handler...)".into(),
+								}),
+							};
+
+							let mut result = Ok(Value::nil());
+							for item in items.drain(1..) {
+								result = run(item, env);
+								if result.is_err() {
+									break
+								}
+							}
+
+							// a limit cannot be caught, as the program could otherwise
+							// carry on past it
+							let error = match result {
+								Ok(value) => return Ok(value),
+								Err(error) if error.kind == ErrorKind::ResourceLimit => return Err(error),
+								Err(error) => error,
+							};
+
+							// a thrown value is caught as it was, and any other error as a
+							// map describing it
+							let caught = match error.kind {
+								ErrorKind::UserError(Thrown(thrown)) => Value::clone(&thrown),
+								kind => Value::map([
+									(
+										Value::Keyword(Symbol("kind".into())),
+										Value::Keyword(Symbol(kind_name(&kind))),
+									),
+									(
+										Value::Keyword(Symbol("message".into())),
+										Value::String(error.message),
+									),
+								]),
+							};
+
+							let new_env = Environment {
+								locals: Scope::child(&env.locals),
+								..env.clone()
+							};
+							new_env.locals.define(symbol, caught);
+
+							let mut body = vec![ Value::Symbol(Symbol("block".into())) ];
+							body.extend(handler);
+
+							value = Value::List(body.into());
+							frame = Some(new_env);
+							continue
+						},
+						"and" | "all" => match items.pop() {
+							// the last argument is only reached if all others are truthy
+							Some(last) if !items.is_empty() => {
+								for item in items.drain(1..) {
+									let nil = run(item, env)?;
+									if !nil.truthy() {
+										return Ok(nil)
+									}
+								}
+
+								value = last;
+								continue
+							},
+							_ => Ok(Value::nil()),
+						},
+						"or" | "any" => match items.pop() {
+							Some(last) if !items.is_empty() => {
+								for item in items.drain(1..) {
+									let non_nil = run(item, env)?;
+									if non_nil.truthy() {
+										return Ok(non_nil)
+									}
+								}
+
+								value = last;
+								continue
+							},
+							_ => Ok(Value::nil()),
+						},
+						// named arguments are taken from calls, and so only found here
+						// outside of one
+						"named-arg" => Err(Error {
+							kind: ErrorKind::SyntaxError,
+							location: None, // todo
+							source: None,
+							message: match named_arg(&Value::List(items.into())) {
+								Some((name, _)) => format!(
+									"{}: names an argument outside of a call to a defined function",
+									name.value(),
 								),
-							})
-						}
-
-						let mut new_locals = HashMap::new();
-						let mut i = 0;
-						for arg in args {
-							i += 1;
-							new_locals.insert(arg.clone(), run(items[i].clone(), env)?);
-						}
-
-						let mut new_env = Environment {
-							locals: new_locals,
-							..env.clone()
-						};
-
-						let mut last = None;
-						for value in body {
-							last = Some(run(value.clone(), &mut new_env)?);
-						}
-
-						Ok(last.unwrap())
+								None => "malformed named argument".into(),
+							},
+						}),
+						// likewise for spread arguments
+						"splat" => Err(Error {
+							kind: ErrorKind::SyntaxError,
+							location: None, // todo
+							source: None,
+							message: match splat(&Value::List(items.into())) {
+								Some(spread) => format!("..{} spreads arguments outside of a call", spread.to_source()),
+								None => "malformed spread argument".into(),
+							},
+						}),
+						"define" | "set!" => {
+							let variable = match items.get(1) {
+								Some(Value::Symbol(variable)) if items.len() == 3 => variable.clone(),
+								_ => return Err(Error {
+									kind: ErrorKind::ArgumentError,
+									location: None, // todo
+									source: None,
+									message: format!("{} requires a symbol and a value", name),
+								}),
+							};
+
+							let defining = name.as_str() == "define";
+							let value = run(items.pop().unwrap(), env)?;
+
+							// define binds in the innermost scope, whilst set! changes the
+							// binding the symbol already has, wherever it is
+							if defining {
+								env.locals.define(variable.clone(), value);
+								return Ok(Value::Symbol(variable))
+							}
+
+							match env.locals.set(&variable, value.clone()) {
+								Ok(()) => Ok(value),
+								Err(value) => match env.symbols.get_mut(&variable) {
+									Some(global) => {
+										*global = value.clone();
+										Ok(value)
+									},
+									None => Err(Error {
+										kind: ErrorKind::NameError,
+										location: None, // todo
+										source: None,
+										message: format!("cannot set undefined symbol {:?}", variable.value()),
+									}),
+								},
+							}
+						},
+						// the definitions of a module are bound as its name (or that
+						// given after as) followed by / and their own
+						"import" => {
+							let (path, name) = match &items[1..] {
+								[ Value::String(path) ] => (path, None),
+								[ Value::String(path), Value::Symbol(Symbol(r#as)), Value::Symbol(name) ]
+									if r#as == "as" => (path, Some(name.clone())),
+								_ => return Err(Error {
+									kind: ErrorKind::ArgumentError,
+									location: None, // todo
+									source: None,
+									message: "import requires a path, optionally followed by as and a name".into(),
+								}),
+							};
+
+							let name = match name {
+								Some(name) => name,
+								None => Path::new(path)
+									.file_stem()
+									.and_then(|stem| Symbol::new(stem.to_string_lossy().into()))
+									.ok_or_else(|| Error {
+										kind: ErrorKind::ArgumentError,
+										location: None, // todo
+										source: None,
+										message: format!("cannot name module {:?} by its path (use as)", path),
+									})?,
+							};
+
+							for (definition, value) in module::import(path, env)?.iter() {
+								let qualified = Symbol(format!("{}/{}", name.value(), definition.value()));
+								env.locals.define(qualified, value.clone());
+							}
+
+							Ok(Value::Symbol(name))
+						},
+						"export" => {
+							let names = items
+								.drain(1..)
+								.map(|item| match item {
+									Value::Symbol(name) => Ok(name),
+									other => Err(Error {
+										kind: ErrorKind::ArgumentError,
+										location: None, // todo
+										source: None,
+										message: format!("export expects symbols, not {}", other.type_name()),
+									}),
+								})
+								.collect::<Result<Vec<_>, _>>()?;
+
+							module::export(names, env)?;
+							Ok(Value::nil())
+						},
+						// fn is another spelling of fun
+						"fun" | "fn" | "def" | "defmacro" => {
+							let is_fun = matches!(name.as_str(), "fun" | "fn");
+							let basis = if is_fun { 0 } else { 1 };
+
+							let args = match items.get(basis + 1) {
+								Some(Value::List(list)) => list,
+								_ => return Err(Error {
+									kind: ErrorKind::ArgumentError,
+									location: None, // todo
+									source: None,
+									message: "expected list to define function arguments".into(),
+								}),
+							};
+
+							// a string before the rest of the body documents it, rather than
+							// being evaluated
+							let mut body = &items[basis + 2..];
+							let doc = match body {
+								[ Value::String(doc), _, .. ] => {
+									body = &body[1..];
+									Some(doc.clone())
+								},
+								_ => None,
+							};
+							let body = body.to_vec();
+
+							// each argument is a pattern, which may take apart the value
+							// given for it
+							let function = if name.as_str() == "defmacro" {
+								// the arguments of a macro are one pattern, matched against the
+								// forms of a call, so that ..rest takes any after the others;
+								// they have no defaults, so a list among them is refused
+								// rather than taken as one
+								let location = args.location().or(location);
+								if let Some(arg) = args.iter().find(|arg| matches!(arg, Value::List(_)) && splat(arg).is_none()) {
+									return Err(Error {
+										kind: ErrorKind::ArgumentError,
+										location,
+										source: None,
+										message: format!(
+											"macro argument {} cannot have a default (use a vector to take a form apart)",
+											arg.to_source(),
+										),
+									})
+								}
+
+								Function::Macro {
+									args: Pattern::parse(&Value::List(args.clone()), location)?,
+									doc,
+									body,
+								}
+							} else {
+								// a function captures the scope it is created in, which it
+								// sees (along with any changes to it) whenever called
+								Function::Native {
+									args: Param::parse_all(args, location)?,
+									doc,
+									body,
+									captured: env.locals.clone(),
+								}
+							};
+
+							// fun gives the function itself, rather than defining it
+							if is_fun {
+								return Ok(Value::Function(Rc::new(function)))
+							}
+
+							let symbol = match &items[1] {
+								Value::Symbol(symbol) => symbol.clone(),
+								_ => return Err(Error {
+									kind: ErrorKind::ArgumentError,
+									location: None, // todo
+									source: None,
+									message: "expected symbol to identify definition".into(),
+								}),
+							};
+
+							env.functions.insert(symbol.clone(), Rc::new(function));
+
+							Ok(Value::Symbol(symbol))
+						},
+						// a variable holding a function is called in preference to a
+						// defined function of the same name
+						_ => match env.locals.get(symbol).or_else(|| env.symbols.get(symbol).cloned()) {
+							Some(function @ Value::Function(_)) => {
+								items[0] = function;
+								value = located(items, location);
+								continue
+							},
+							_ => match env.functions.get(symbol) {
+								Some(function) => {
+									items[0] = Value::Function(function.clone());
+									value = located(items, location);
+									continue
+								},
+								None => Err(not_found(symbol, env, || format!("no defined function {:?}", name))),
+							},
+						},
+					},
+					// a call giving a function may itself be called
+					Value::List(_) => match run(std::mem::take(&mut items[0]), env)? {
+						function @ Value::Function(_) => {
+							items[0] = function;
+							value = located(items, location);
+							continue
+						},
+						other => Err(Error {
+							kind: ErrorKind::TypeError,
+							location: None, // todo
+							source: None,
+							message: format!("{} is not callable", other.type_name()),
+						}),
+					},
+					Value::Function(function) => match &*function.clone() {
+						// a macro is given its arguments unevaluated, and the form it
+						// gives is evaluated in place of the call
+						Function::Macro { args, body, .. } => {
+							value = expand_macro(args, body, items.drain(1..).collect(), env)?;
+							continue
+						},
+						function @ Function::Native { args: params, .. } => {
+							let args = call_args(params, items.drain(1..).collect(), location, env)?;
+
+							// the last expression of the body is evaluated in place of the
+							// call, within the new environment
+							match enter(function, args, location, env)? {
+								Some((last, new_env)) => {
+									value = last;
+									frame = Some(new_env);
+									// a recur within the function cannot return to a loop
+									// around its call
+									target = None;
+									continue
+								},
+								None => Ok(Value::nil()),
+							}
+						},
+						Function::Provided(fun) => (*fun)(&spread_args(items.drain(1..), env)?),
+						Function::Host(fun) => fun(&spread_args(items.drain(1..), env)?),
+						Function::ProvidedWithEnv(fun) => {
+							let args = spread_args(items.drain(1..), env)?;
+							(*fun)(&args, env)
+						},
 					},
-					Some(Function::Provided(fun)) => (*fun)(
-						&items[1..]
-							.iter()
-							.map(|item| run(item.clone(), env))
-							.collect::<Result<Vec<_>, _>>()?
-					),
-					None => Err(Error {
-						kind: ErrorKind::NameError,
+					other => Err(Error {
+						kind: ErrorKind::TypeError,
 						location: None, // todo
-						message: format!("no defined function {:?}", &name),
-					})
-				},
+						source: None,
+						message: format!("{} is not callable (use quote)", other.type_name())
+					}),
+				}
 			},
-			other => Err(Error {
+			// unlike a list, a vector is data, so only its elements are evaluated
+			Value::Vector(items) => Ok(Value::Vector(
+				items
+					.into_iter()
+					.map(|item| run(item, env))
+					.collect::<Result<_, _>>()?,
+			)),
+			Value::Map(entries) => Ok(Value::map(
+				entries
+					.into_iter()
+					.map(|(key, value)| Ok((run(key, env)?, run(value, env)?)))
+					.collect::<Result<Vec<_>, _>>()?,
+			)),
+			Value::Symbol(symbol) => {
+				if let Some(local) = env.locals.get(&symbol) {
+					Ok(local)
+				} else if let Some(global) = env.symbols.get(&symbol) {
+					Ok(global.clone())
+				} else if let Some(function) = env.functions.get(&symbol) {
+					// a defined function may be referred to as a value
+					Ok(Value::Function(function.clone()))
+				} else {
+					Err(not_found(&symbol, env, || format!("symbol {:?} not found (use quote)", symbol.value())))
+				}
+			}
+			_ => Ok(value),
+		};
+	}
+}
+
+// the form a macro gives for the forms of a call to it, which it is given as
+// they were written
+pub(crate) fn expand_macro(
+	args: &Pattern,
+	body: &[Value],
+	forms: Vec<Value>,
+	env: &Environment,
+) -> Result<Value, Error> {
+	if let Pattern::Sequence { items, rest, .. } = args {
+		let max = if rest.is_some() { usize::MAX } else { items.len() };
+		check_arity(items.len(), max, forms.len(), None)?;
+	}
+
+	let mut macro_env = Environment {
+		locals: Rc::new(Scope::default()),
+		..env.clone()
+	};
+	args.bind(Value::List(forms.into()), &macro_env.locals)?;
+
+	let mut expansion = Value::nil();
+	for value in body {
+		expansion = run(value.clone(), &mut macro_env)?;
+	}
+
+	Ok(expansion)
+}
+
+// calls a function with arguments already evaluated, as for a function given
+// to another, counting as a step of the limits (so that a provided function
+// calling another for each item counts a step for each)
+pub fn call(function: &Value, args: Vec<Value>, env: &mut Environment) -> Result<Value, Error> {
+	env.limits.step()?;
+
+	let function = match function {
+		Value::Function(function) => function.clone(),
+		other => return Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None, // todo
+			source: None,
+			message: format!("{} is not callable", other.type_name()),
+		}),
+	};
+
+	match &*function {
+		function @ Function::Native { args: params, .. } => {
+			check_arity(required(params), params.len(), args.len(), None)?;
+
+			match enter(function, args.into_iter().map(Some).collect(), None, env)? {
+				Some((last, mut new_env)) => run(last, &mut new_env),
+				None => Ok(Value::nil()),
+			}
+		},
+		Function::Provided(fun) => (*fun)(&args),
+		Function::Host(fun) => fun(&args),
+		Function::ProvidedWithEnv(fun) => (*fun)(&args, env),
+		Function::Macro { .. } => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None, // todo
+			source: None,
+			message: "macro cannot be called with evaluated arguments".into(),
+		}),
+	}
+}
+
+// binds the arguments of a native function in a scope within the one it
+// captured (evaluating the default of each parameter without one there, after
+// those before it are bound), and evaluates all but the last expression of its
+// body, giving the last and the environment to evaluate it in (or none for an
+// empty body). errors in the arguments are reported at the call, if it has a
+// location
+fn enter(
+	function: &Function,
+	args: Vec<Option<Value>>,
+	location: Option<LocationArea>,
+	env: &Environment,
+) -> Result<Option<(Value, Environment)>, Error> {
+	let (params, body, captured) = match function {
+		Function::Native { args, body, captured, .. } => (args, body, captured),
+		_ => unreachable!(),
+	};
+
+	let mut new_env = Environment {
+		locals: Scope::child(captured),
+		..env.clone()
+	};
+	let args = args.into_iter().chain(std::iter::repeat(None));
+	for (param, arg) in params.iter().zip(args) {
+		let arg = match (arg, &param.default) {
+			(Some(arg), _) => arg,
+			(None, Some(default)) => run(default.clone(), &mut new_env)?,
+			(None, None) => return Err(Error {
+				kind: ErrorKind::ArgumentError,
+				location,
+				source: None,
+				message: format!("missing argument {}", param.pattern),
+			}),
+		};
+
+		param.pattern.bind(arg, &new_env.locals)?;
+	}
+
+	let (last, body) = match body.split_last() {
+		Some(split) => split,
+		None => return Ok(None),
+	};
+
+	for value in body {
+		run(value.clone(), &mut new_env)?;
+	}
+
+	Ok(Some((last.clone(), new_env)))
+}
+
+// evaluates the arguments of a call in order, placing any given by name (which
+// must follow those given by position) at the parameter of that name, and
+// leaving the rest to their defaults; errors are reported at the call
+fn call_args(
+	params: &[Param],
+	mut items: Vec<Value>,
+	location: Option<LocationArea>,
+	env: &mut Environment,
+) -> Result<Vec<Option<Value>>, Error> {
+	let positional = items.iter().take_while(|item| named_arg(item).is_none()).count();
+	let named = items.split_off(positional);
+	let positional = spread_args(items, env)?;
+
+	if named.is_empty() || positional.len() > params.len() {
+		check_arity(required(params), params.len(), positional.len(), location)?;
+	}
+
+	let mut args = positional
+		.into_iter()
+		.map(Some)
+		.chain(std::iter::repeat(None))
+		.take(params.len())
+		.collect::<Vec<_>>();
+
+	for item in named {
+		let (name, value) = named_arg(&item).ok_or_else(|| Error {
+			kind: ErrorKind::ArgumentError,
+			location,
+			source: None,
+			message: "positional argument after named arguments".into(),
+		})?;
+
+		let index = params
+			.iter()
+			.position(|param| matches!(&param.pattern, Pattern::Symbol(symbol) if symbol == name))
+			.ok_or_else(|| Error {
+				kind: ErrorKind::ArgumentError,
+				location,
+				source: None,
+				message: format!("no parameter named {}", name.value()),
+			})?;
+
+		if args[index].is_some() {
+			return Err(Error {
+				kind: ErrorKind::ArgumentError,
+				location,
+				source: None,
+				message: format!("argument {} provided more than once", name.value()),
+			})
+		}
+
+		args[index] = Some(run(value.clone(), env)?);
+	}
+
+	Ok(args)
+}
+
+// evaluates the arguments of a call in order, with the items of each spread
+// with ..x taking its place
+fn spread_args(
+	items: impl IntoIterator<Item = Value>,
+	env: &mut Environment,
+) -> Result<Vec<Value>, Error> {
+	let mut args = Vec::new();
+	for item in items {
+		let spread = match splat(&item) {
+			Some(spread) => spread,
+			None => {
+				args.push(run(item, env)?);
+				continue
+			},
+		};
+
+		match run(spread.clone(), env)? {
+			Value::List(list) => args.extend(list.into_vec()),
+			Value::Vector(vector) => args.extend(vector),
+			// reported where ..x was written, which is read as (splat x)
+			other => return Err(Error {
 				kind: ErrorKind::TypeError,
-				location: None, // todo
-				message: format!("{} is not callable (use quote)", other.type_name())
+				location: match &item {
+					Value::List(list) => list.location(),
+					_ => None,
+				},
+				source: None,
+				message: format!(
+					"cannot spread {} from ..{} (expected a list or vector)",
+					other.type_name(), spread.to_source(),
+				),
 			}),
+		}
+	}
+
+	Ok(args)
+}
+
+// the error for a symbol which is not bound, which for a name within a namespace
+// (as math/flor) that has others names those closest to it
+fn not_found(symbol: &Symbol, env: &Environment, message: impl FnOnce() -> String) -> Error {
+	let message = match symbol.value().rsplit_once('/') {
+		Some((namespace, name)) => {
+			let names = env.functions
+				.keys()
+				.chain(env.symbols.keys())
+				.cloned()
+				.chain(env.locals.names())
+				.filter_map(|other| Some(other.value().strip_prefix(namespace)?.strip_prefix('/')?.to_string()))
+				.collect::<Vec<_>>();
+
+			let mut close = names
+				.iter()
+				.map(|other| (distance(name, other), other))
+				.filter(|(distance, _)| *distance <= (name.chars().count() / 3).max(2))
+				.collect::<Vec<_>>();
+			close.sort();
+			close.dedup();
+
+			let close = close
+				.iter()
+				.take(3)
+				.map(|(_, other)| format!("{}/{}", namespace, other))
+				.collect::<Vec<_>>();
+
+			match (names.is_empty(), close.is_empty()) {
+				(true, _) => message(),
+				(false, true) => format!("no {:?} in namespace {}", name, namespace),
+				(false, false) => format!(
+					"no {:?} in namespace {} (close matches: {})",
+					name, namespace, close.join(", "),
+				),
+			}
 		},
-		Value::Symbol(symbol) => {
-			if let Some(local) = env.locals.get(&symbol) {
-				Ok(local.clone())
-			} else if let Some(global) = env.symbols.get(&symbol) {
-				Ok(global.clone())
-			} else {
-				Err(Error {
-					kind: ErrorKind::NameError,
-					location: None, // todo
-					message: format!("symbol {:?} not found (use quote)", symbol.value()),
-				})
+		None => message(),
+	};
+
+	Error {
+		kind: ErrorKind::NameError,
+		location: None, // todo
+		source: None,
+		message,
+	}
+}
+
+// the number of characters which must be inserted, removed, or replaced to turn
+// one string into another
+fn distance(a: &str, b: &str) -> usize {
+	let b = b.chars().collect::<Vec<_>>();
+	let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+	for (i, a) in a.chars().enumerate() {
+		let mut current = vec![ i + 1 ];
+		for (j, b) in b.iter().enumerate() {
+			let replace = previous[j] + (a != *b) as usize;
+			current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+		}
+
+		previous = current;
+	}
+
+	previous[b.len()]
+}
+
+// the name and value of a named argument, read from name: value
+fn named_arg(item: &Value) -> Option<(&Symbol, &Value)> {
+	let list = match item {
+		Value::List(list) => list,
+		_ => return None,
+	};
+
+	let mut items = list.iter();
+	match (items.next(), items.next(), items.next(), items.next()) {
+		(Some(Value::Symbol(Symbol(head))), Some(Value::Symbol(name)), Some(value), None)
+			if head == "named-arg" => Some((name, value)),
+		_ => None,
+	}
+}
+
+// the expression whose items are spread as arguments, read from ..x
+pub(crate) fn splat(item: &Value) -> Option<&Value> {
+	let list = match item {
+		Value::List(list) => list,
+		_ => return None,
+	};
+
+	let mut items = list.iter();
+	match (items.next(), items.next(), items.next()) {
+		(Some(Value::Symbol(Symbol(head))), Some(value), None) if head == "splat" => Some(value),
+		_ => None,
+	}
+}
+
+// a call rebuilt from its items, such as once its head is looked up, which
+// keeps the location of the call it was taken apart from
+fn located(items: Vec<Value>, location: Option<LocationArea>) -> Value {
+	let list = List::from(items);
+	Value::List(match location {
+		Some(location) => list.located(location),
+		None => list,
+	})
+}
+
+// the number of parameters without defaults, which come before any with them
+fn required(params: &[Param]) -> usize {
+	params.iter().take_while(|param| param.default.is_none()).count()
+}
+
+fn check_arity(min: usize, max: usize, provided: usize, location: Option<LocationArea>) -> Result<(), Error> {
+	if provided < min || provided > max {
+		// a maximum of usize::MAX is none at all
+		let expected = if min == max {
+			min.to_string()
+		} else if max == usize::MAX {
+			format!("at least {}", min)
+		} else {
+			format!("{} to {}", min, max)
+		};
+
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location,
+			source: None,
+			message: format!("{} arguments provided ({} expected)", provided, expected),
+		})
+	}
+
+	Ok(())
+}
+
+// the kind of an error as a keyword name, such as type-error for TypeError
+fn kind_name(kind: &ErrorKind) -> String {
+	let mut name = String::new();
+	for (i, ch) in format!("{:?}", kind).chars().enumerate() {
+		if ch.is_ascii_uppercase() {
+			if i > 0 {
+				name.push('-');
 			}
+
+			name.push(ch.to_ascii_lowercase());
+		} else {
+			name.push(ch);
 		}
-		_ => Ok(value),
 	}
+
+	name
+}
+
+// evaluates the unquoted parts of a quasiquoted template, splicing in the
+// elements of lists from unquote-splicing; depth counts the quasiquotes around
+// the template, as only unquotes matching the outermost one are evaluated
+fn quasiquote(value: Value, depth: usize, env: &mut Environment) -> Result<Value, Error> {
+	let (items, is_vector) = match value {
+		Value::List(items) => (items.into_vec(), false),
+		Value::Vector(items) => (items, true),
+		Value::Map(entries) => return Ok(Value::map(
+			entries
+				.into_iter()
+				.map(|(key, value)| Ok((
+					quasiquote(key, depth, env)?,
+					quasiquote(value, depth, env)?,
+				)))
+				.collect::<Result<Vec<_>, _>>()?,
+		)),
+		other => return Ok(other),
+	};
+
+	if !is_vector {
+		if let Some(inner) = unquoted(&items, "unquote") {
+			if depth == 1 {
+				return run(inner.clone(), env)
+			}
+		}
+
+		if depth == 1 && unquoted(&items, "unquote-splicing").is_some() {
+			return Err(Error {
+				kind: ErrorKind::SyntaxError,
+				location: None, // todo
+				source: None,
+				message: "unquote-splicing outside of a list".into(),
+			})
+		}
+
+		// a nested quasiquote or unquote is kept, with its contents at a new depth
+		let nested = if unquoted(&items, "quasiquote").is_some() {
+			Some(depth + 1)
+		} else if
+			unquoted(&items, "unquote").is_some() ||
+			unquoted(&items, "unquote-splicing").is_some()
+		{
+			Some(depth - 1)
+		} else {
+			None
+		};
+
+		if let Some(depth) = nested {
+			let inner = quasiquote(items[1].clone(), depth, env)?;
+			return Ok(Value::List(vec![ items[0].clone(), inner ].into()))
+		}
+	}
+
+	let mut output = Vec::new();
+	for item in items {
+		match &item {
+			Value::List(list) => if let (1, Some(inner)) = (depth, unquoted(list, "unquote-splicing")) {
+				match run(inner.clone(), env)? {
+					Value::List(spliced) => output.extend(spliced.into_vec()),
+					Value::Vector(spliced) => output.extend(spliced),
+					other => return Err(Error {
+						kind: ErrorKind::TypeError,
+						location: None, // todo
+						source: None,
+						message: format!("cannot splice {} into list", other.type_name()),
+					}),
+				}
+			} else {
+				output.push(quasiquote(item, depth, env)?);
+			},
+			Value::Vector(_) | Value::Map(_) => output.push(quasiquote(item, depth, env)?),
+			_ => output.push(item),
+		}
+	}
+
+	Ok(if is_vector { Value::Vector(output) } else { Value::List(output.into()) })
+}
+
+fn unquoted<'a>(items: impl IntoIterator<Item = &'a Value>, form: &str) -> Option<&'a Value> {
+	let mut items = items.into_iter();
+	match (items.next(), items.next(), items.next()) {
+		(Some(Value::Symbol(Symbol(name))), Some(inner), None) if name == form => Some(inner),
+		_ => None,
+	}
+}
+
+// the patterns of a loop and its body, along with the scope it began in
+struct LoopTarget {
+	patterns: Vec<Pattern>,
+	body: Vec<Value>,
+	locals: Rc<Scope>,
 }
 
 #[derive(Clone, Default)]
 pub struct Environment {
 	pub symbols: HashMap<Symbol, Value>,
-	pub locals: HashMap<Symbol, Value>,
-	pub functions: HashMap<Symbol, Function>,
+	pub locals: Rc<Scope>,
+	// shared by every reference to each, so that all are the same function
+	pub functions: HashMap<Symbol, Rc<Function>>,
+	// the file being evaluated, which imports are found relative to
+	pub file: Option<Rc<Path>>,
+	pub modules: Rc<RefCell<Modules>>,
+	pub readers: Readers,
+	// where each macro expanded before evaluation was defined, for errors in its
+	// expansion
+	pub macro_locations: HashMap<Symbol, LocationArea>,
+	pub limits: Limits,
+}
+
+// local variables, within which those of the scope around (if any) are visible;
+// scopes are shared, so that a change to a variable is seen by all within it
+#[derive(Default)]
+pub struct Scope {
+	variables: RefCell<HashMap<Symbol, Value>>,
+	parent: Option<Rc<Scope>>,
+}
+
+impl Environment {
+	// defines a function of the host, which programs call by name (or pass to
+	// other functions) as any other
+	pub fn register_fn(
+		&mut self,
+		name: &str,
+		function: impl Fn(&[Value]) -> Result<Value, Error> + 'static,
+	) {
+		let name = Symbol::new(name.into()).unwrap_or_else(|| panic!("invalid function name {:?}", name));
+		self.functions.insert(name, Rc::new(Function::Host(Rc::new(function))));
+	}
+
+	// reads forms tagged #tag in sources evaluated in the environment with the
+	// given reader, in place of any provided for the tag
+	pub fn register_reader(&mut self, tag: &str, reader: Reader) {
+		let tag = Symbol::new(tag.into()).unwrap_or_else(|| panic!("invalid reader tag {:?}", tag));
+		self.readers.insert(tag, reader);
+	}
+}
+
+impl Scope {
+	pub fn child(parent: &Rc<Self>) -> Rc<Self> {
+		Rc::new(Self {
+			variables: RefCell::default(),
+			parent: Some(parent.clone()),
+		})
+	}
+
+	// the outermost scope around this one, which holds the variables of the
+	// program itself
+	pub fn root(scope: &Rc<Self>) -> Rc<Self> {
+		match &scope.parent {
+			Some(parent) => Self::root(parent),
+			None => scope.clone(),
+		}
+	}
+
+	// the variables bound in this scope (but not those around it), by name
+	pub fn variables(&self) -> Vec<(Symbol, Value)> {
+		let mut variables = self.variables
+			.borrow()
+			.iter()
+			.map(|(symbol, value)| (symbol.clone(), value.clone()))
+			.collect::<Vec<_>>();
+		variables.sort_by(|(a, _), (b, _)| a.value().cmp(b.value()));
+
+		variables
+	}
+
+	// the names of the variables bound in this scope or those around it
+	pub fn names(&self) -> Vec<Symbol> {
+		let mut names = self.variables.borrow().keys().cloned().collect::<Vec<_>>();
+		if let Some(parent) = &self.parent {
+			names.extend(parent.names());
+		}
+
+		names
+	}
+
+	// the value of the variable in the nearest scope binding it
+	pub fn get(&self, symbol: &Symbol) -> Option<Value> {
+		match self.variables.borrow().get(symbol) {
+			Some(value) => Some(value.clone()),
+			None => self.parent.as_ref()?.get(symbol),
+		}
+	}
+
+	// binds the variable in this scope, replacing any binding of it here
+	pub fn define(&self, symbol: Symbol, value: Value) {
+		self.variables.borrow_mut().insert(symbol, value);
+	}
+
+	// changes the variable in the nearest scope binding it, or gives the value
+	// back if none do
+	pub fn set(&self, symbol: &Symbol, value: Value) -> Result<(), Value> {
+		if let Some(variable) = self.variables.borrow_mut().get_mut(symbol) {
+			*variable = value;
+			return Ok(())
+		}
+
+		match &self.parent {
+			Some(parent) => parent.set(symbol, value),
+			None => Err(value),
+		}
+	}
+}
+
+// a parameter of a native function, and the expression giving its value when
+// no argument is given for it (if it can be left out)
+#[derive(Clone, Debug)]
+pub struct Param {
+	pub pattern: Pattern,
+	pub default: Option<Value>,
+}
+
+impl Param {
+	// a parameter is a pattern, or a list of a pattern and its default; those
+	// with defaults must follow those without. errors are reported where the
+	// list was written, or else at the form defining the function
+	fn parse_all(values: &List, location: Option<LocationArea>) -> Result<Vec<Self>, Error> {
+		let location = values.location().or(location);
+		let mut params = Vec::new();
+		for value in values {
+			let param = match value {
+				value if splat(value).is_some() => return Err(Error {
+					kind: ErrorKind::ArgumentError,
+					location,
+					source: None,
+					message: "arguments cannot be spread into the parameters of a function".into(),
+				}),
+				Value::List(list) => match list.iter().collect::<Vec<_>>()[..] {
+					[ pattern, default ] => Self {
+						pattern: Pattern::parse(pattern, location)?,
+						default: Some(default.clone()),
+					},
+					_ => return Err(Error {
+						kind: ErrorKind::ArgumentError,
+						location,
+						source: None,
+						message: "expected a pattern and its default in parameter list".into(),
+					}),
+				},
+				pattern => Self { pattern: Pattern::parse(pattern, location)?, default: None },
+			};
+
+			if param.default.is_none() && params.iter().any(|param: &Self| param.default.is_some()) {
+				return Err(Error {
+					kind: ErrorKind::ArgumentError,
+					location,
+					source: None,
+					message: format!("parameter {} without a default follows one with", param.pattern),
+				})
+			}
+
+			params.push(param);
+		}
+
+		Ok(params)
+	}
+}
+
+// written as in source, such as (greeting "hello")
+impl fmt::Display for Param {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.default {
+			Some(default) => write!(f, "({} {:#})", self.pattern, default),
+			None => write!(f, "{}", self.pattern),
+		}
+	}
 }
 
+pub type HostFn = dyn Fn(&[Value]) -> Result<Value, Error>;
+
 #[derive(Clone)]
 pub enum Function {
 	Native {
-		args: Vec<Symbol>,
+		args: Vec<Param>,
+		doc: Option<String>,
 		body: Vec<Value>,
+		captured: Rc<Scope>,
 	},
 	Provided(fn(&[Value]) -> Result<Value, Error>),
+	// provided functions which call others, and so need an environment to
+	// evaluate them in
+	ProvidedWithEnv(fn(&[Value], &mut Environment) -> Result<Value, Error>),
+	// functions registered by the host, which (unlike those provided) may hold
+	// state of their own
+	Host(Rc<HostFn>),
+	Macro {
+		// a list pattern, which the forms of a call are matched against as a list
+		args: Pattern,
+		doc: Option<String>,
+		body: Vec<Value>,
+	},
+}
+
+impl Function {
+	// the string documenting a function or macro, given before its body
+	pub fn doc(&self) -> Option<&str> {
+		match self {
+			Self::Native { doc, .. } | Self::Macro { doc, .. } => doc.as_deref(),
+			Self::Provided(_) | Self::ProvidedWithEnv(_) | Self::Host(_) => None,
+		}
+	}
+}
+
+// functions are only equal to themselves (as shared by function values)
+impl PartialEq for Function {
+	fn eq(&self, other: &Self) -> bool {
+		std::ptr::eq(self, other)
+	}
+}
+
+// written as #<function (args)>, which cannot be read back
+impl fmt::Display for Function {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let (kind, args): (_, Vec<_>) = match self {
+			Self::Native { args, .. } => ("function", args.iter().map(Param::to_string).collect()),
+			Self::Macro { args, .. } => return write!(f, "#<macro {}>", args),
+			Self::Provided(_) | Self::ProvidedWithEnv(_) | Self::Host(_) => return write!(f, "#<function>"),
+		};
+
+		write!(f, "#<{} ({})>", kind, args.join(" "))
+	}
+}
+
+impl fmt::Debug for Function {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Display::fmt(self, f)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Environment;
+	use crate::{ eval, Value, Error, ErrorKind };
+
+	fn run(src: &str) -> Result<Value, ErrorKind> {
+		eval(src, None).map_err(|error| error.kind)
+	}
+
+	// the line and column the error evaluating the source is reported at
+	fn error_at(src: &str) -> Option<(usize, usize)> {
+		let error = eval(src, None).unwrap_err();
+		error.location.map(|location| (location.start.line, location.start.column))
+	}
+
+	#[test]
+	fn vectors() {
+		assert!(matches!(run("[1 2 3]"), Ok(Value::Vector(items)) if items.len() == 3));
+		// the items are evaluated, but not called
+		assert_eq!(run("(let [x 1] [x (add x 1)])").unwrap().to_string(), "[1 2]");
+		assert_eq!(run("(1 2 3)").unwrap_err(), ErrorKind::TypeError);
+	}
+
+	// far deeper than the stack would allow without reusing frames, though short
+	// of the million asked for, which takes a while in a debug build
+	#[test]
+	fn tail_calls() {
+		let sum = "
+			(def sum (n acc) (if (lt n 20000) (sum (add n 1) (add acc n)) acc))
+			(sum 0 0)
+		";
+		assert_eq!(run(sum).unwrap().to_string(), "199990000");
+
+		let parity = "
+			(def even? (n) (if (eq n 0) true (odd? (sub n 1))))
+			(def odd? (n) (if (eq n 0) false (even? (sub n 1))))
+			(even? 20001)
+		";
+		assert_eq!(run(parity).unwrap().to_string(), "false");
+	}
+
+	#[test]
+	fn loops() {
+		let sum = "(loop [i 0 acc 0] (if (lt i 20000) (recur (add i 1) (add acc i)) acc))";
+		assert_eq!(run(sum).unwrap().to_string(), "199990000");
+
+		assert_eq!(run("(loop [i 0] (add 1 (recur i)))").unwrap_err(), ErrorKind::SyntaxError);
+		assert_eq!(run("(recur 1)").unwrap_err(), ErrorKind::SyntaxError);
+	}
+
+	#[test]
+	fn try_catch() {
+		assert_eq!(run("(try (throw 5) (catch e (add e 1)))").unwrap().to_string(), "6");
+		// errors of the runtime are caught too, described by a map
+		assert_eq!(run("(try (car 5) (catch e (get e :kind)))").unwrap().to_string(), ":type-error");
+
+		let rethrown = "(try (try (throw 5) (catch e (throw (add e 1)))) (catch e e))";
+		assert_eq!(run(rethrown).unwrap().to_string(), "6");
+		assert!(matches!(
+			run("(try (throw 1) (catch e (throw e)))").unwrap_err(),
+			ErrorKind::UserError(thrown) if *thrown.0 == Value::Integer(1),
+		));
+
+		// the handler is only run on failure
+		assert_eq!(run("(try (add 1 2) (catch e 0))").unwrap().to_string(), "3");
+	}
+
+	#[test]
+	fn let_scopes() {
+		// bindings shadow those around them, each seeing those before it, and are
+		// dropped after the body
+		assert_eq!(run("(define x 10) (let [x 1 y (+ x 1)] y)").unwrap(), Value::Integer(2));
+		assert_eq!(run("(define x 10) (let [x 1 y (+ x 1)] y) x").unwrap(), Value::Integer(10));
+		assert_eq!(run("(let [y 1] y) y").unwrap_err(), ErrorKind::NameError);
+
+		// as are those of a let within another
+		let nested = "(let [x 1] [(let [x 2] (let [x 3] x)) (let [x (+ x 10)] x) x])";
+		assert_eq!(run(nested).unwrap().to_string(), "[3 11 1]");
+	}
+
+	#[test]
+	fn define_and_set() {
+		assert_eq!(run("(set! x 1)").unwrap_err(), ErrorKind::NameError);
+		assert_eq!(run("(define x 1) (set! x 2) x").unwrap().to_string(), "2");
+
+		// set! changes the binding of the scope enclosing it
+		assert_eq!(run("(define x 1) (let [y 0] (set! x 2)) x").unwrap().to_string(), "2");
+		assert_eq!(run("(define f (fun () (set! y 1))) (f)").unwrap_err(), ErrorKind::NameError);
+	}
+
+	#[test]
+	fn function_identity() {
+		assert_eq!(run("(def f () 1) (= f f)").unwrap(), Value::Boolean(true));
+		assert_eq!(run("(= car car)").unwrap(), Value::Boolean(true));
+		assert_eq!(run("(def f () 1) (define a f) (define b f) (= a b)").unwrap(), Value::Boolean(true));
+
+		// though two definitions alike are not the same function
+		assert_eq!(run("(def f () 1) (def g () 1) (= f g)").unwrap(), Value::Boolean(false));
+	}
+
+	#[test]
+	fn register_fn() {
+		let mut env = Environment::default();
+		env.register_fn("square", |args| match args {
+			[ Value::Integer(integer) ] => Ok(Value::Integer(integer * integer)),
+			_ => Err(Error {
+				kind: ErrorKind::TypeError,
+				location: None,
+				source: None,
+				message: "square expects an integer".into(),
+			}),
+		});
+
+		let mut run = |src| eval(src, Some(&mut env)).map(|value| value.to_string());
+		assert_eq!(run("(square 7)").unwrap(), "49");
+		assert_eq!(run("(map square [1 2 3])").unwrap(), "[1 4 9]");
+		assert_eq!(run("(apply square [5])").unwrap(), "25");
+		assert_eq!(run("(square \"7\")").unwrap_err().kind, ErrorKind::TypeError);
+	}
+
+	#[test]
+	fn call_locations() {
+		let def = "(def f (a (b 2)) a)\n";
+		assert_eq!(error_at(&format!("{}[\n\t(f)]", def)), Some((3, 2)));
+		assert_eq!(error_at(&format!("{}(f 1 2 3)", def)), Some((2, 1)));
+		assert_eq!(error_at(&format!("{}(f b: 1)", def)), Some((2, 1)));
+		assert_eq!(error_at(&format!("{}(f b: 1 2)", def)), Some((2, 1)));
+		assert_eq!(error_at(&format!("{}(f 1 c: 1)", def)), Some((2, 1)));
+		assert_eq!(error_at(&format!("{}(f 1 a: 1)", def)), Some((2, 1)));
+	}
+
+	#[test]
+	fn spread_location() {
+		assert_eq!(error_at("(+ 1\n\t..2)"), Some((2, 2)));
+		assert_eq!(error_at("(def f (a) a)\n(f 1 ..:a)"), Some((2, 6)));
+	}
+
+	#[test]
+	fn match_location() {
+		assert_eq!(error_at("(def f (n)\n\t(match n (0 :zero)))\n(f 1)"), Some((2, 2)));
+	}
+
+	// run on a small stack, which calls taking a frame each would soon overflow
+	#[test]
+	fn tail_positions() {
+		std::thread::Builder::new()
+			.stack_size(256 * 1024)
+			.spawn(|| {
+				let count = "
+					(def count (n) (let [m (- n 1)] (block (cond ((< m 0) 0) (true (count m))))))
+					(count 10000)
+				";
+				assert_eq!(run(count).unwrap().to_string(), "0");
+
+				let walk = "(def walk (n) (match n (0 :done) (_ (walk (- n 1))))) (walk 10000)";
+				assert_eq!(run(walk).unwrap().to_string(), ":done");
+
+				let parity = "
+					(def even? (n) (if (= n 0) true (odd? (- n 1))))
+					(def odd? (n) (if (= n 0) false (even? (- n 1))))
+					(even? 10001)
+				";
+				assert_eq!(run(parity).unwrap().to_string(), "false");
+			})
+			.unwrap()
+			.join()
+			.unwrap();
+	}
 }
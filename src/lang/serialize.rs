@@ -0,0 +1,153 @@
+use super::{ Value, Symbol };
+
+use serde::de::{ self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor };
+use serde::ser::{ self, Serialize, Serializer, SerializeMap };
+
+use std::fmt;
+
+// nil is written as none (null), lists, vectors, and sets as sequences, chars as
+// strings of one char, and keywords and symbols as strings of their names; other
+// values (such as functions) cannot be written
+impl Serialize for Value {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			Self::Integer(integer) => serializer.serialize_i64(*integer),
+			Self::Number(number) => serializer.serialize_f64(*number),
+			Self::Char(ch) => serializer.serialize_char(*ch),
+			Self::String(string) => serializer.serialize_str(string),
+			Self::Boolean(boolean) => serializer.serialize_bool(*boolean),
+			Self::List(list) if list.is_empty() => serializer.serialize_none(),
+			Self::List(list) => serializer.collect_seq(list),
+			Self::Vector(items) => serializer.collect_seq(items),
+			Self::Set(set) => serializer.collect_seq(set),
+			Self::Map(entries) => {
+				let mut map = serializer.serialize_map(Some(entries.len()))?;
+				for (key, value) in entries {
+					map.serialize_entry(key, value)?;
+				}
+				map.end()
+			},
+			Self::Symbol(symbol) | Self::Keyword(symbol) => serializer.serialize_str(symbol.value()),
+			Self::Function(_) | Self::Reduced(_) | Self::Foreign(_) => Err(ser::Error::custom(
+				format!("{} cannot be serialized", self.type_name()),
+			)),
+		}
+	}
+}
+
+// none (null) is read as nil and sequences as vectors, whilst the keys of maps
+// are read as keywords where they are strings which could name one (and as
+// strings otherwise)
+impl<'de> Deserialize<'de> for Value {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserializer.deserialize_any(ValueVisitor)
+	}
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+	type Value = Value;
+
+	fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "a value")
+	}
+
+	fn visit_bool<E: de::Error>(self, boolean: bool) -> Result<Value, E> {
+		Ok(Value::Boolean(boolean))
+	}
+
+	fn visit_i64<E: de::Error>(self, integer: i64) -> Result<Value, E> {
+		Ok(Value::Integer(integer))
+	}
+
+	// integers too large for an i64 become numbers, as in arithmetic
+	fn visit_u64<E: de::Error>(self, integer: u64) -> Result<Value, E> {
+		Ok(match i64::try_from(integer) {
+			Ok(integer) => Value::Integer(integer),
+			Err(_) => Value::Number(integer as f64),
+		})
+	}
+
+	fn visit_f64<E: de::Error>(self, number: f64) -> Result<Value, E> {
+		Ok(Value::Number(number))
+	}
+
+	fn visit_char<E: de::Error>(self, ch: char) -> Result<Value, E> {
+		Ok(Value::Char(ch))
+	}
+
+	fn visit_str<E: de::Error>(self, string: &str) -> Result<Value, E> {
+		Ok(Value::String(string.into()))
+	}
+
+	fn visit_string<E: de::Error>(self, string: String) -> Result<Value, E> {
+		Ok(Value::String(string))
+	}
+
+	fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+		Ok(Value::nil())
+	}
+
+	fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+		Ok(Value::nil())
+	}
+
+	fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+		Value::deserialize(deserializer)
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+		let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+		while let Some(item) = seq.next_element()? {
+			items.push(item);
+		}
+
+		Ok(Value::Vector(items))
+	}
+
+	fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+		let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0));
+		while let Some((key, value)) = map.next_entry::<Value, Value>()? {
+			let key = match key {
+				Value::String(string) => match Symbol::new(string.clone()) {
+					Some(symbol) => Value::Keyword(symbol),
+					None => Value::String(string),
+				},
+				key => key,
+			};
+
+			entries.push((key, value));
+		}
+
+		Ok(Value::map(entries))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{ eval, Value };
+
+	#[test]
+	fn round_trip() {
+		let value = eval(r#"
+			{:name "facsimile" :version [0 1 {:pre nil}] :ratio 0.5 :tags ["a" "b"] "not a keyword" true}
+		"#, None).unwrap();
+
+		let json = serde_json::to_string(&value).unwrap();
+		assert_eq!(
+			json,
+			r#"{"name":"facsimile","version":[0,1,{"pre":null}],"ratio":0.5,"tags":["a","b"],"not a keyword":true}"#,
+		);
+
+		let read: Value = serde_json::from_str(&json).unwrap();
+		assert_eq!(read, value);
+	}
+
+	#[test]
+	fn function() {
+		let function = eval("[1 (fun (x) x)]", None).unwrap();
+		let error = serde_json::to_string(&function).unwrap_err();
+		assert_eq!(error.to_string(), "function cannot be serialized");
+	}
+}
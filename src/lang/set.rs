@@ -0,0 +1,105 @@
+use super::Value;
+
+use std::collections::hash_map::{ DefaultHasher, RandomState };
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{ BuildHasher, Hash, Hasher };
+
+// the items of a set in the order they were first added, along with the
+// positions of those with each hash, so that finding an item takes constant
+// time rather than searching them all
+#[derive(Clone, Default)]
+pub struct Set {
+	items: Vec<Value>,
+	index: HashMap<u64, Vec<usize>>,
+	state: RandomState,
+}
+
+impl Set {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	// adds the item unless an equal one is already there, giving whether it was
+	// added
+	pub fn insert(&mut self, item: Value) -> bool {
+		if self.contains(&item) {
+			return false
+		}
+
+		let hash = self.state.hash_one(&item);
+		self.index.entry(hash).or_default().push(self.items.len());
+		self.items.push(item);
+
+		true
+	}
+
+	pub fn contains(&self, item: &Value) -> bool {
+		match self.index.get(&self.state.hash_one(item)) {
+			Some(positions) => positions.iter().any(|&i| self.items[i] == *item),
+			None => false,
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.items.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.items.is_empty()
+	}
+
+	// the items in the order they were added
+	pub fn iter(&self) -> std::slice::Iter<'_, Value> {
+		self.items.iter()
+	}
+}
+
+impl<'a> IntoIterator for &'a Set {
+	type Item = &'a Value;
+	type IntoIter = std::slice::Iter<'a, Value>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.iter()
+	}
+}
+
+// items equal to one before them are left out
+impl FromIterator<Value> for Set {
+	fn from_iter<I: IntoIterator<Item = Value>>(iter: I) -> Self {
+		let mut set = Self::new();
+		for item in iter {
+			set.insert(item);
+		}
+
+		set
+	}
+}
+
+// sets are equal when they have the same items, regardless of order
+impl PartialEq for Set {
+	fn eq(&self, other: &Self) -> bool {
+		self.len() == other.len() && self.iter().all(|item| other.contains(item))
+	}
+}
+
+// as is their hash, which combines that of each item (hashed alike for every
+// set, unlike those of the index)
+impl Hash for Set {
+	fn hash<H: Hasher>(&self, hasher: &mut H) {
+		let combined = self.iter().fold(0u64, |combined, item| {
+			let mut item_hasher = DefaultHasher::new();
+			item.hash(&mut item_hasher);
+			combined.wrapping_add(item_hasher.finish())
+		});
+
+		self.len().hash(hasher);
+		combined.hash(hasher);
+	}
+}
+
+impl fmt::Debug for Set {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_set().entries(self).finish()
+	}
+}
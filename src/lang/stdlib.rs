@@ -1,30 +1,145 @@
-use super::{ Value, Symbol, Error, ErrorKind };
-use super::runtime::Function;
-
+use super::{ Value, Symbol, Error, ErrorKind, LocationArea, Thrown };
+use super::{ expand, lexer, parser };
+use super::ast::Ast;
+use super::limits::Limits;
+use super::list::List;
+use super::set::Set;
+use super::runtime::{ self, Function, Environment, Scope };
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::rc::Rc;
 
-pub fn index() -> HashMap<Symbol, Function> {
+pub fn index() -> HashMap<Symbol, Rc<Function>> {
 	macro_rules! fns {
-		[ $( $fn:ident ),* $(,)? ] => {
+		[ $( $fn:ident $( as $name:literal )? ),* $(,)? ] => {
 			maplit::hashmap! {
 				$(
-					Symbol(stringify!($fn).into()) => Function::Provided($fn),
+					Symbol(fns!(@name $fn $( $name )?).into()) => Function::Provided($fn),
 				)*
 			}
 		};
+		(@name $fn:ident) => { stringify!($fn) };
+		(@name $fn:ident $name:literal) => { $name };
+	}
+
+	let mut index = fns![
+		not, eq, ne, lt, gt, lte, gte, add, sub, mul, div, rem, quot, r#mod as "mod",
+		abs, floor, ceil, round, sqrt, pow, min, max, get, length,
+		assoc, keys, num, keyword, name, char_to_code as "char->code",
+		code_to_char as "code->char", char_upcase as "char-upcase",
+		char_downcase as "char-downcase", is_infinite as "infinite?",
+		is_nan as "nan?", is_finite as "finite?", cat, print, pr, input, time,
+		srand, rand, throw, set, union, intersection, difference,
+		contains as "contains?", remove, length as "count", car, cdr, cons,
+		is_list as "list?", is_empty as "empty?", cat as "str", substring, split,
+		join, upper, lower, doc, reduced, is_reduced as "reduced?",
+
+		// operator spellings
+		add as "+", sub as "-", mul as "*", eq as "=", lt as "<", gt as ">",
+		lte as "<=", gte as ">=", not_eq as "not=",
+	];
+
+	// functions which call those given to them
+	index.extend(maplit::hashmap! {
+		Symbol("apply".into()) => Function::ProvidedWithEnv(apply),
+		Symbol("map".into()) => Function::ProvidedWithEnv(map),
+		Symbol("filter".into()) => Function::ProvidedWithEnv(filter),
+		Symbol("reduce".into()) => Function::ProvidedWithEnv(reduce),
+		Symbol("sort".into()) => Function::ProvidedWithEnv(sort),
+	});
+
+	// functions whose work counts toward the steps the limits allow
+	index.extend(maplit::hashmap! {
+		Symbol("range".into()) => Function::ProvidedWithEnv(range),
+	});
+
+	// functions which see the definitions (and readers) of the program
+	index.extend(maplit::hashmap! {
+		Symbol("macroexpand".into()) => Function::ProvidedWithEnv(macroexpand),
+		Symbol("read".into()) => Function::ProvidedWithEnv(read),
+		Symbol("eval".into()) => Function::ProvidedWithEnv(eval),
+	});
+
+	// functions reaching files and standard input, which a host may leave out
+	#[cfg(feature = "io")]
+	index.extend(maplit::hashmap! {
+		Symbol("slurp".into()) => Function::Provided(slurp),
+		Symbol("spit".into()) => Function::Provided(spit),
+		Symbol("read-line".into()) => Function::Provided(read_line),
+	});
+
+	// each function is also found within its namespace, as math/floor, for when
+	// its plain name is taken (unless it was left out), sharing the one function
+	let mut index = index
+		.into_iter()
+		.map(|(name, function)| (name, Rc::new(function)))
+		.collect::<HashMap<_, _>>();
+	for (namespace, names) in NAMESPACES {
+		for name in *names {
+			if let Some(function) = index.get(&Symbol((*name).into())).cloned() {
+				index.insert(Symbol(format!("{}/{}", namespace, name)), function);
+			}
+		}
+	}
+
+	index
+}
+
+// the provided functions which a program whose limits deny it input and output
+// may not use
+const IO: &[&str] = &[ "print", "pr", "input", "slurp", "spit", "read-line" ];
+
+// the provided functions, with those the limits given deny refusing to run (so
+// that a program calling one fails as it would reaching any other limit)
+pub fn index_within(limits: &Limits) -> HashMap<Symbol, Rc<Function>> {
+	let mut index = index();
+	if !limits.allows_io() {
+		for (name, function) in index.iter_mut() {
+			let name = name.value();
+			if IO.contains(&name.strip_prefix("io/").unwrap_or(name)) {
+				*function = Rc::new(Function::Provided(denied_io));
+			}
+		}
 	}
 
-	fns![
-		not, eq, ne, lt, gt, lte, gte, add, sub, mul, div, rem, get, num, cat,
-		print, input, time, srand, rand,
-	]
+	index
+}
+
+fn denied_io(_: &[Value]) -> Result<Value, Error> {
+	Err(Error {
+		kind: ErrorKind::ResourceLimit,
+		location: None,
+		source: None,
+		message: "input and output are not allowed".into(),
+	})
 }
 
+const NAMESPACES: &[(&str, &[&str])] = &[
+	("math", &[
+		"add", "sub", "mul", "div", "rem", "quot", "mod", "abs", "floor", "ceil",
+		"round", "sqrt", "pow", "min", "max", "num", "infinite?", "nan?",
+		"finite?", "srand", "rand",
+	]),
+	("str", &[
+		"str", "substring", "split", "join", "upper", "lower", "keyword", "name",
+		"char->code", "code->char", "char-upcase", "char-downcase",
+	]),
+	("list", &[
+		"get", "length", "count", "car", "cdr", "cons", "list?", "empty?", "assoc",
+		"keys", "set", "union", "intersection", "difference", "contains?", "remove",
+		"range", "apply", "map", "filter", "reduce", "reduced", "reduced?", "sort",
+	]),
+	("io", &[ "print", "pr", "input", "time", "slurp", "spit", "read-line" ]),
+];
+
 fn not(args: &[Value]) -> Result<Value, Error> {
 	if args.len() != 1 {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
+			source: None,
 			message: "not requires one argument".into(),
 		})
 	}
@@ -37,12 +152,28 @@ fn eq(args: &[Value]) -> Result<Value, Error> {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
+			source: None,
 			message: "eq requires at least two arguments".into(),
 		})
 	}
 
 	let first = &args[0];
-	Ok(Value::Boolean(args.iter().all(|item| item == first)))
+	Ok(Value::Boolean(args.iter().all(|item| equal(item, first))))
+}
+
+// the opposite of eq, so that (not= 1 2 2) holds whereas (ne 1 2 2) does not
+fn not_eq(args: &[Value]) -> Result<Value, Error> {
+	if args.len() < 2 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "not= requires at least two arguments".into(),
+		})
+	}
+
+	let first = &args[0];
+	Ok(Value::Boolean(!args.iter().all(|item| equal(item, first))))
 }
 
 fn ne(args: &[Value]) -> Result<Value, Error> {
@@ -50,13 +181,14 @@ fn ne(args: &[Value]) -> Result<Value, Error> {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
+			source: None,
 			message: "ne requires at least two arguments".into(),
 		})
 	}
 
 	for a in 1..args.len() {
 		for b in 0..a {
-			if args[a] == args[b] {
+			if equal(&args[a], &args[b]) {
 				return Ok(Value::Boolean(false))
 			}
 		}
@@ -65,284 +197,328 @@ fn ne(args: &[Value]) -> Result<Value, Error> {
 	Ok(Value::Boolean(true))
 }
 
+// integers and numbers are compared by value, so (eq 1 1.0) holds
+fn equal(a: &Value, b: &Value) -> bool {
+	match (a, b) {
+		(Value::Integer(a), Value::Number(b)) => *a as f64 == *b,
+		(Value::Number(a), Value::Integer(b)) => *a == *b as f64,
+		(Value::List(a), Value::List(b)) =>
+			a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b)),
+		(Value::Vector(a), Value::Vector(b)) =>
+			a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b)),
+		// maps are equal regardless of the order of their entries
+		(Value::Map(a), Value::Map(b)) =>
+			a.len() == b.len() && a.iter().all(|(key, a)| {
+				b.iter().any(|(other, b)| key == other && equal(a, b))
+			}),
+		// as are sets regardless of the order of their items
+		(Value::Set(a), Value::Set(b)) =>
+			a.len() == b.len() && a.iter().all(|item| b.contains(item)),
+		(a, b) => a == b,
+	}
+}
+
 fn lt(args: &[Value]) -> Result<Value, Error> {
-	if args.len() != 2 {
+	ordered("lt", args, |ordering| ordering == Ordering::Less)
+}
+
+fn gt(args: &[Value]) -> Result<Value, Error> {
+	ordered("gt", args, |ordering| ordering == Ordering::Greater)
+}
+
+fn lte(args: &[Value]) -> Result<Value, Error> {
+	ordered("lte", args, |ordering| ordering != Ordering::Greater)
+}
+
+fn gte(args: &[Value]) -> Result<Value, Error> {
+	ordered("gte", args, |ordering| ordering != Ordering::Less)
+}
+
+// whether each argument is ordered as given against the next, so (lt 1 2 3)
+// holds; nan is ordered against nothing
+fn ordered(
+	name: &str,
+	args: &[Value],
+	holds: fn(Ordering) -> bool,
+) -> Result<Value, Error> {
+	if args.len() < 2 {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
-			message: "lt requires two arguments".into(),
+			source: None,
+			message: format!("{} requires at least two arguments", name),
 		})
 	}
 
-	let type_error = Err(Error {
-		kind: ErrorKind::TypeError,
-		location: None,
-		message: "lt only takes numbers".into(),
-	});
+	// every pair is compared (even once one does not hold), so that values which
+	// cannot be compared are always an error
+	let orderings = args
+		.windows(2)
+		.map(|pair| compare(name, &pair[0], &pair[1]))
+		.collect::<Result<Vec<_>, _>>()?;
 
-	Ok(Value::Boolean(
-		match args[0] {
-			Value::Number(number) => number,
-			_ => return type_error,
-		} <
-		match args[1] {
-			Value::Number(number) => number,
-			_ => return type_error,
-		}
-	))
+	Ok(Value::Boolean(orderings.into_iter().all(|ordering| ordering.is_some_and(holds))))
 }
 
-fn gt(args: &[Value]) -> Result<Value, Error> {
-	if args.len() != 2 {
-		return Err(Error {
-			kind: ErrorKind::ArgumentError,
+// numbers (of either kind) are ordered by value, and chars and strings by their
+// code points, but no other values can be compared
+fn compare(name: &str, a: &Value, b: &Value) -> Result<Option<Ordering>, Error> {
+	Ok(match (a, b) {
+		(Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+		(Value::Integer(_) | Value::Number(_), Value::Integer(_) | Value::Number(_)) =>
+			float(a).partial_cmp(&float(b)),
+		(Value::Char(a), Value::Char(b)) => Some(a.cmp(b)),
+		(Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+		_ => return Err(Error {
+			kind: ErrorKind::TypeError,
 			location: None,
-			message: "gt requires two arguments".into(),
-		})
+			source: None,
+			message: format!("{} cannot compare {} with {}", name, a.type_name(), b.type_name()),
+		}),
+	})
+}
+
+fn add(args: &[Value]) -> Result<Value, Error> {
+	// adding to a set gives the set with the rest of the arguments as items
+	if let Some(Value::Set(set)) = args.first() {
+		return Value::set(set.iter().chain(&args[1..]).cloned())
 	}
 
-	let type_error = Err(Error {
-		kind: ErrorKind::TypeError,
-		location: None,
-		message: "gt only takes numbers".into(),
-	});
+	arithmetic("add", args)?;
+	Ok(fold(args, i64::checked_add, |a, b| a + b))
+}
 
-	Ok(Value::Boolean(
-		match args[0] {
-			Value::Number(number) => number,
-			_ => return type_error,
-		} >
-		match args[1] {
-			Value::Number(number) => number,
-			_ => return type_error,
-		}
+fn sub(args: &[Value]) -> Result<Value, Error> {
+	arithmetic("sub", args)?;
+	Ok(fold(args, i64::checked_sub, |a, b| a - b))
+}
+
+fn mul(args: &[Value]) -> Result<Value, Error> {
+	arithmetic("mul", args)?;
+	Ok(fold(args, i64::checked_mul, |a, b| a * b))
+}
+
+fn div(args: &[Value]) -> Result<Value, Error> {
+	arithmetic("div", args)?;
+	Ok(fold(
+		args,
+		|a, b| match a.checked_rem(b) {
+			Some(0) => a.checked_div(b),
+			_ => None,
+		},
+		|a, b| a / b,
 	))
 }
 
-fn lte(args: &[Value]) -> Result<Value, Error> {
-	if args.len() != 2 {
-		return Err(Error {
-			kind: ErrorKind::ArgumentError,
-			location: None,
-			message: "lte requires two arguments".into(),
-		})
-	}
+fn rem(args: &[Value]) -> Result<Value, Error> {
+	arithmetic("rem", args)?;
 
-	let type_error = Err(Error {
-		kind: ErrorKind::TypeError,
-		location: None,
-		message: "lte only takes numbers".into(),
-	});
+	let divisor = fold(&args[1..], i64::checked_mul, |a, b| a * b);
+	Ok(fold(&[args[0].clone(), divisor], i64::checked_rem, |a, b| a % b))
+}
 
-	Ok(Value::Boolean(
-		match args[0] {
-			Value::Number(number) => number,
-			_ => return type_error,
-		} <=
-		match args[1] {
-			Value::Number(number) => number,
-			_ => return type_error,
-		}
+// division rounding towards zero, as rem gives the remainder of
+fn quot(args: &[Value]) -> Result<Value, Error> {
+	binary("quot", args)?;
+	Ok(fold(args, i64::checked_div, |a, b| (a / b).trunc()))
+}
+
+// the remainder of division rounding down, which takes the sign of the divisor
+// (unlike rem, which takes that of the dividend)
+fn r#mod(args: &[Value]) -> Result<Value, Error> {
+	binary("mod", args)?;
+	Ok(fold(
+		args,
+		|a, b| a.checked_rem(b).map(|r| if r != 0 && (r < 0) != (b < 0) { r + b } else { r }),
+		|a, b| a - b * (a / b).floor(),
 	))
 }
 
-fn gte(args: &[Value]) -> Result<Value, Error> {
+fn pow(args: &[Value]) -> Result<Value, Error> {
+	binary("pow", args)?;
+	Ok(fold(
+		args,
+		|a, b| u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+		f64::powf,
+	))
+}
+
+fn binary(name: &str, args: &[Value]) -> Result<(), Error> {
 	if args.len() != 2 {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
-			message: "gte requires two arguments".into(),
+			source: None,
+			message: format!("{} requires two arguments", name),
 		})
 	}
 
-	let type_error = Err(Error {
-		kind: ErrorKind::TypeError,
-		location: None,
-		message: "gte only takes numbers".into(),
-	});
+	numbers(name, args)
+}
 
-	Ok(Value::Boolean(
-		match args[0] {
-			Value::Number(number) => number,
-			_ => return type_error,
-		} >=
-		match args[1] {
-			Value::Number(number) => number,
-			_ => return type_error,
-		}
-	))
+fn abs(args: &[Value]) -> Result<Value, Error> {
+	unary("abs", args)?;
+	Ok(match args[0] {
+		Value::Integer(integer) => match integer.checked_abs() {
+			Some(abs) => Value::Integer(abs),
+			None => Value::Number((integer as f64).abs()),
+		},
+		ref number => Value::Number(float(number).abs()),
+	})
 }
 
-fn add(args: &[Value]) -> Result<Value, Error> {
-	if args.len() < 2 {
-		return Err(Error {
-			kind: ErrorKind::ArgumentError,
-			location: None,
-			message: "add requires at least two arguments".into(),
-		})
-	}
+fn floor(args: &[Value]) -> Result<Value, Error> {
+	unary("floor", args)?;
+	Ok(rounded(&args[0], f64::floor))
+}
 
-	if
-		args
-			.iter()
-			.any(|item| if let Value::Number(_) = item { false } else { true })
-	{
-		return Err(Error {
-			kind: ErrorKind::TypeError,
-			location: None,
-			message: "add only takes numbers".into(),
-		})
-	}
+fn ceil(args: &[Value]) -> Result<Value, Error> {
+	unary("ceil", args)?;
+	Ok(rounded(&args[0], f64::ceil))
+}
 
-	Ok(Value::Number(
-		args
-			.iter()
-			.map(|item| match item {
-				Value::Number(number) => number,
-				_ => unreachable!(),
-			})
-			.sum()
-	))
+// halfway values are rounded away from zero
+fn round(args: &[Value]) -> Result<Value, Error> {
+	unary("round", args)?;
+	Ok(rounded(&args[0], f64::round))
 }
 
-fn sub(args: &[Value]) -> Result<Value, Error> {
-	if args.len() < 2 {
-		return Err(Error {
-			kind: ErrorKind::ArgumentError,
-			location: None,
-			message: "sub requires at least two arguments".into(),
-		})
+// a number rounded to an integer, unless it is too large to be one (or is inf or
+// nan), in which case it stays a number
+fn rounded(value: &Value, round: fn(f64) -> f64) -> Value {
+	let number = match value {
+		Value::Integer(integer) => return Value::Integer(*integer),
+		value => round(float(value)),
+	};
+
+	if number >= i64::MIN as f64 && number < i64::MAX as f64 {
+		Value::Integer(number as i64)
+	} else {
+		Value::Number(number)
 	}
+}
 
-	if
-		args
-			.iter()
-			.any(|item| if let Value::Number(_) = item { false } else { true })
-	{
+// the square root of a negative number is an error, rather than nan
+fn sqrt(args: &[Value]) -> Result<Value, Error> {
+	unary("sqrt", args)?;
+
+	let number = float(&args[0]);
+	if number < 0.0 {
 		return Err(Error {
-			kind: ErrorKind::TypeError,
+			kind: ErrorKind::ArgumentError,
 			location: None,
-			message: "sub only takes numbers".into(),
+			source: None,
+			message: format!("sqrt of negative number {}", args[0]),
 		})
 	}
 
-	Ok(Value::Number(
-		match args[0] {
-			Value::Number(number) => number,
-			_ => unreachable!(),
-		} -
-		args[1..]
-			.iter()
-			.map(|item| match item {
-				Value::Number(number) => number,
-				_ => unreachable!(),
-			})
-			.sum::<f32>()
-	))
+	Ok(Value::Number(number.sqrt()))
 }
 
-fn mul(args: &[Value]) -> Result<Value, Error> {
-	if args.len() < 2 {
+fn unary(name: &str, args: &[Value]) -> Result<(), Error> {
+	if args.len() != 1 {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
-			message: "mul requires at least two arguments".into(),
+			source: None,
+			message: format!("{} requires one argument", name),
 		})
 	}
 
-	if
-		args
-			.iter()
-			.any(|item| if let Value::Number(_) = item { false } else { true })
-	{
-		return Err(Error {
-			kind: ErrorKind::TypeError,
-			location: None,
-			message: "mul only takes numbers".into(),
-		})
-	}
+	numbers(name, args)
+}
 
-	Ok(Value::Number(
-		args
-			.iter()
-			.map(|item| match item {
-				Value::Number(number) => number,
-				_ => unreachable!(),
-			})
-			.product()
-	))
+fn min(args: &[Value]) -> Result<Value, Error> {
+	extreme("min", args, Ordering::Less)
 }
 
-fn div(args: &[Value]) -> Result<Value, Error> {
-	if args.len() < 2 {
+fn max(args: &[Value]) -> Result<Value, Error> {
+	extreme("max", args, Ordering::Greater)
+}
+
+// the first of the arguments ordered furthest towards one end, or nan if any is
+fn extreme(name: &str, args: &[Value], end: Ordering) -> Result<Value, Error> {
+	if args.is_empty() {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
-			message: "div requires at least two arguments".into(),
+			source: None,
+			message: format!("{} requires at least one argument", name),
 		})
 	}
 
-	if
-		args
-			.iter()
-			.any(|item| if let Value::Number(_) = item { false } else { true })
-	{
-		return Err(Error {
-			kind: ErrorKind::TypeError,
-			location: None,
-			message: "div only takes numbers".into(),
-		})
+	numbers(name, args)?;
+
+	let mut extreme = &args[0];
+	for item in args {
+		match compare(name, item, extreme)? {
+			Some(ordering) if ordering == end => extreme = item,
+			Some(_) => (),
+			None => return Ok(Value::Number(f64::NAN)),
+		}
 	}
 
-	Ok(Value::Number(
-		match args[0] {
-			Value::Number(number) => number,
-			_ => unreachable!(),
-		} /
-		args[1..]
-			.iter()
-			.map(|item| match item {
-				Value::Number(number) => number,
-				_ => unreachable!(),
-			})
-			.product::<f32>()
-	))
+	Ok(extreme.clone())
 }
 
-fn rem(args: &[Value]) -> Result<Value, Error> {
+fn arithmetic(name: &str, args: &[Value]) -> Result<(), Error> {
 	if args.len() < 2 {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
-			message: "rem requires at least two arguments".into(),
+			source: None,
+			message: format!("{} requires at least two arguments", name),
 		})
 	}
 
+	numbers(name, args)
+}
+
+// integer arithmetic stays exact until a float is involved or the integer
+// operation fails (overflow, etc), at which point the result becomes a float
+fn fold(
+	args: &[Value],
+	integer: fn(i64, i64) -> Option<i64>,
+	float: fn(f64, f64) -> f64,
+) -> Value {
+	let mut result = args[0].clone();
+
+	for item in &args[1..] {
+		result = match (&result, item) {
+			(Value::Integer(a), Value::Integer(b)) => match integer(*a, *b) {
+				Some(value) => Value::Integer(value),
+				None => Value::Number(float(*a as f64, *b as f64)),
+			},
+			(a, b) => Value::Number(float(self::float(a), self::float(b))),
+		};
+	}
+
+	result
+}
+
+fn numbers(name: &str, args: &[Value]) -> Result<(), Error> {
 	if
 		args
 			.iter()
-			.any(|item| if let Value::Number(_) = item { false } else { true })
+			.any(|item| !matches!(item, Value::Integer(_) | Value::Number(_)))
 	{
 		return Err(Error {
 			kind: ErrorKind::TypeError,
 			location: None,
-			message: "rem only takes numbers".into(),
+			source: None,
+			message: format!("{} only takes numbers", name),
 		})
 	}
 
-	Ok(Value::Number(
-		match args[0] {
-			Value::Number(number) => number,
-			_ => unreachable!(),
-		} %
-		args[1..]
-			.iter()
-			.map(|item| match item {
-				Value::Number(number) => number,
-				_ => unreachable!(),
-			})
-			.product::<f32>()
-	))
+	Ok(())
+}
+
+fn float(value: &Value) -> f64 {
+	match value {
+		Value::Integer(integer) => *integer as f64,
+		Value::Number(number) => *number,
+		_ => unreachable!(),
+	}
 }
 
 fn get(args: &[Value]) -> Result<Value, Error> {
@@ -350,31 +526,53 @@ fn get(args: &[Value]) -> Result<Value, Error> {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
+			source: None,
 			message: "get requires 2-4 arguments".into(),
 		})
 	}
 
-	let (list, is_string) = match &args[0] {
-		Value::List(list) => (list.clone(), false),
-		Value::String(string) => (
-			string.chars()
-				.map(|ch| Value::String(ch.to_string()))
-				.collect::<Vec<_>>(),
-			true,
-		),
+	// a map is indexed by key, giving nil for a missing key
+	if let Value::Map(map) = &args[0] {
+		if args.len() != 2 {
+			return Err(Error {
+				kind: ErrorKind::ArgumentError,
+				location: None,
+				source: None,
+				message: "get requires two arguments for a map".into(),
+			})
+		}
+
+		return Ok(
+			map.iter()
+				.find(|(key, _)| *key == args[1])
+				.map_or(Value::nil(), |(_, value)| value.clone())
+		)
+	}
+
+	let is_string = matches!(args[0], Value::String(_));
+	let is_vector = matches!(args[0], Value::Vector(_));
+	let list = match &args[0] {
+		Value::List(list) => list.iter().cloned().collect(),
+		Value::Vector(vector) => vector.clone(),
+		Value::String(string) => string.chars()
+			.map(|ch| Value::String(ch.to_string()))
+			.collect::<Vec<_>>(),
 		_ => return Err(Error {
 			kind: ErrorKind::TypeError,
 			location: None,
-			message: "get expects a list or string".into(),
+			source: None,
+			message: "get expects a list, vector, map, or string".into(),
 		}),
 	};
 
 	let indices = args[1..].iter()
 		.map(|arg| match arg {
+			Value::Integer(integer) => Ok(*integer as isize),
 			Value::Number(number) => Ok(number.round() as isize),
 			_ => Err(Error {
 				kind: ErrorKind::TypeError,
 				location: None,
+				source: None,
 				message: "get expects numerical indices".into(),
 			}),
 		})
@@ -388,6 +586,7 @@ fn get(args: &[Value]) -> Result<Value, Error> {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
+			source: None,
 			message: "step cannot be zero".into(),
 		})
 	}
@@ -408,8 +607,10 @@ fn get(args: &[Value]) -> Result<Value, Error> {
 	if end == start {
 		return if is_string {
 			Ok(Value::String("".into()))
+		} else if is_vector {
+			Ok(Value::Vector(Vec::new()))
 		} else {
-			Ok(Value::List(Vec::new()))
+			Ok(Value::nil())
 		}
 	}
 
@@ -417,6 +618,7 @@ fn get(args: &[Value]) -> Result<Value, Error> {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
+			source: None,
 			message: "step does not match indices".into(),
 		})
 	}
@@ -450,109 +652,1180 @@ fn get(args: &[Value]) -> Result<Value, Error> {
 		)
 	} else if output.len() == 1 && args.len() == 2 {
 		std::mem::take(&mut output[0])
+	} else if is_vector {
+		Value::Vector(output)
 	} else {
-		Value::List(output)
+		Value::List(output.into())
 	})
 }
 
-fn num(args: &[Value]) -> Result<Value, Error> {
+fn length(args: &[Value]) -> Result<Value, Error> {
 	if args.len() != 1 {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
-			message: "num requires one argument".into(),
+			source: None,
+			message: "length requires one argument".into(),
 		})
 	}
 
-	use std::str::FromStr;
-	Ok(Value::Number(match &args[0] {
-		Value::Number(number) => *number,
-		Value::String(string) => match f32::from_str(&string) {
-			Ok(number) => number,
-			Err(_) => return Ok(Value::nil()),
-		},
-		Value::Boolean(boolean) => if *boolean { 1f32 } else { 0f32 },
-		Value::List(list) => list.len() as f32,
+	Ok(Value::Integer(match &args[0] {
+		Value::List(list) => list.len(),
+		Value::Vector(vector) => vector.len(),
+		Value::Map(map) => map.len(),
+		Value::Set(set) => set.len(),
+		Value::String(string) => string.chars().count(),
 		_ => return Err(Error {
 			kind: ErrorKind::TypeError,
 			location: None,
-			message: "num expects a number, string, boolean, or list".into(),
+			source: None,
+			message: "length expects a list, vector, map, set, or string".into(),
 		}),
-	}))
-}
-
-fn cat(args: &[Value]) -> Result<Value, Error> {
-	Ok(Value::String(cat_impl(args)))
+	} as i64))
 }
 
-fn cat_impl(args: &[Value]) -> String {
-	let mut output = String::new();
-	for arg in args {
-		match arg {
-			Value::Number(number) => output.push_str(&number.to_string()),
-			Value::String(string) => output.push_str(&string),
-			Value::Boolean(boolean) => output.push_str(&boolean.to_string()),
-			Value::List(list) => output.push_str(&cat_impl(&list)),
-			Value::Symbol(symbol) => output.push_str(symbol.value()),
-		}
-	}
-
-	output
+fn car(args: &[Value]) -> Result<Value, Error> {
+	Ok(list_arg("car", args)?.first().cloned().unwrap_or_default())
 }
 
-fn print(args: &[Value]) -> Result<Value, Error> {
-	println!("{}", cat_impl(args));
-	Ok(Value::nil())
+fn cdr(args: &[Value]) -> Result<Value, Error> {
+	Ok(Value::List(list_arg("cdr", args)?.rest()))
 }
 
-fn input(args: &[Value]) -> Result<Value, Error> {
-	if args.len() > 0 {
+fn list_arg<'a>(name: &str, args: &'a [Value]) -> Result<&'a List, Error> {
+	if args.len() != 1 {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
-			message: "input takes no arguments".into(),
+			source: None,
+			message: format!("{} requires one argument", name),
 		})
 	}
 
-	let mut buf = String::new();
+	match &args[0] {
+		Value::List(list) => Ok(list),
+		_ => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: format!("{} expects a list", name),
+		}),
+	}
+}
 
-	if let Err(err) = std::io::stdin().read_line(&mut buf) {
+// the list with a value in front, sharing the rest with the original
+fn cons(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 2 {
 		return Err(Error {
-			kind: ErrorKind::IoError,
+			kind: ErrorKind::ArgumentError,
 			location: None,
-			message: err.to_string(),
+			source: None,
+			message: "cons requires two arguments".into(),
 		})
 	}
 
-	if buf.ends_with('\n') { buf.pop(); }
-	if buf.ends_with('\r') { buf.pop(); }
-
-	Ok(Value::String(buf))
+	match &args[1] {
+		Value::List(list) => Ok(Value::List(list.cons(args[0].clone()))),
+		_ => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "cons expects a list".into(),
+		}),
+	}
 }
 
-fn time(args: &[Value]) -> Result<Value, Error> {
-	use std::time::{ SystemTime, UNIX_EPOCH };
-
-	if args.len() > 0 {
+// whether the value is a list, including nil (the empty list)
+fn is_list(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 1 {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
-			message: "time takes no arguments".into(),
+			source: None,
+			message: "list? requires one argument".into(),
 		})
 	}
 
-	Ok(
-		SystemTime::now()
-			.duration_since(UNIX_EPOCH)
-			.map(|d| Value::Number(d.as_secs_f32()))
-			.unwrap_or_default()
-	)
+	Ok(Value::Boolean(matches!(args[0], Value::List(_))))
 }
 
-use std::sync::atomic::{ AtomicU64, Ordering };
-
-static RAND_SEED: AtomicU64 = AtomicU64::new(1);
-
+fn is_empty(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "empty? requires one argument".into(),
+		})
+	}
+
+	Ok(Value::Boolean(match &args[0] {
+		Value::List(list) => list.is_empty(),
+		Value::Vector(items) => items.is_empty(),
+		Value::Set(set) => set.is_empty(),
+		Value::Map(map) => map.is_empty(),
+		Value::String(string) => string.is_empty(),
+		_ => return Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "empty? expects a list, vector, map, set, or string".into(),
+		}),
+	}))
+}
+
+fn assoc(args: &[Value]) -> Result<Value, Error> {
+	if args.len() < 3 || args.len().is_multiple_of(2) {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "assoc requires a map followed by keys and values".into(),
+		})
+	}
+
+	let map = match &args[0] {
+		Value::Map(map) => map.clone(),
+		_ => return Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "assoc expects a map".into(),
+		}),
+	};
+
+	let entries = args[1..]
+		.chunks(2)
+		.map(|entry| (entry[0].clone(), entry[1].clone()));
+
+	Ok(Value::map(map.into_iter().chain(entries)))
+}
+
+fn keys(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "keys requires one argument".into(),
+		})
+	}
+
+	match &args[0] {
+		Value::Map(map) => Ok(Value::List(
+			map.iter()
+				.map(|(key, _)| key.clone())
+				.collect()
+		)),
+		_ => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "keys expects a map".into(),
+		}),
+	}
+}
+
+fn set(args: &[Value]) -> Result<Value, Error> {
+	Value::set(args.iter().cloned())
+}
+
+// the items in any of the given sets
+fn union(args: &[Value]) -> Result<Value, Error> {
+	let sets = set_args("union", args)?;
+	Value::set(sets.iter().flat_map(|set| set.iter()).cloned())
+}
+
+// the items of the first set which are in all of the others
+fn intersection(args: &[Value]) -> Result<Value, Error> {
+	let sets = set_args("intersection", args)?;
+	Value::set(
+		sets[0]
+			.iter()
+			.filter(|item| sets[1..].iter().all(|set| set.contains(item)))
+			.cloned()
+	)
+}
+
+// the items of the first set which are in none of the others
+fn difference(args: &[Value]) -> Result<Value, Error> {
+	let sets = set_args("difference", args)?;
+	Value::set(
+		sets[0]
+			.iter()
+			.filter(|item| !sets[1..].iter().any(|set| set.contains(item)))
+			.cloned()
+	)
+}
+
+fn set_args<'a>(name: &str, args: &'a [Value]) -> Result<Vec<&'a Set>, Error> {
+	if args.is_empty() {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: format!("{} requires at least one argument", name),
+		})
+	}
+
+	args.iter()
+		.map(|arg| match arg {
+			Value::Set(set) => Ok(set),
+			_ => Err(Error {
+				kind: ErrorKind::TypeError,
+				location: None,
+				source: None,
+				message: format!("{} expects sets", name),
+			}),
+		})
+		.collect()
+}
+
+// whether a set, list, or vector has the given item, or a map the given key
+fn contains(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 2 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "contains? requires two arguments".into(),
+		})
+	}
+
+	Ok(Value::Boolean(match &args[0] {
+		Value::Set(set) => set.contains(&args[1]),
+		Value::Vector(items) => items.contains(&args[1]),
+		Value::List(list) => list.iter().any(|item| *item == args[1]),
+		Value::Map(map) => map.iter().any(|(key, _)| *key == args[1]),
+		_ => return Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "contains? expects a set, list, vector, or map".into(),
+		}),
+	}))
+}
+
+// the set without any of the given items
+fn remove(args: &[Value]) -> Result<Value, Error> {
+	match args.first() {
+		Some(Value::Set(set)) => Ok(Value::Set(
+			set.iter()
+				.filter(|item| !args[1..].contains(item))
+				.cloned()
+				.collect()
+		)),
+		Some(_) => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "remove expects a set".into(),
+		}),
+		None => Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "remove requires a set followed by items".into(),
+		}),
+	}
+}
+
+fn range(args: &[Value], env: &mut Environment) -> Result<Value, Error> {
+	if !(1..=3).contains(&args.len()) {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "range requires 1-3 arguments".into(),
+		})
+	}
+
+	let bounds = args.iter()
+		.map(|arg| match arg {
+			Value::Integer(integer) => Ok(*integer),
+			_ => Err(Error {
+				kind: ErrorKind::TypeError,
+				location: None,
+				source: None,
+				message: "range expects integers".into(),
+			}),
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+
+	let (start, end) = match bounds[..] {
+		[end] => (0, end),
+		[start, end, ..] => (start, end),
+		_ => unreachable!(),
+	};
+	let step = *bounds.get(2).unwrap_or(&1);
+
+	if step == 0 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "step cannot be zero".into(),
+		})
+	}
+
+	// each item counts as a step, all counted before any is made, so that a range
+	// too long for the limits fails before taking the memory for it
+	let length = match (step > 0 && start < end) || (step < 0 && start > end) {
+		true => (end as i128 - start as i128).unsigned_abs().div_ceil(step.unsigned_abs() as u128),
+		false => 0,
+	};
+	env.limits.step_by(length.try_into().unwrap_or(u64::MAX))?;
+
+	let mut output = Vec::new();
+	let mut i = start;
+
+	while (step > 0 && i < end) || (step < 0 && i > end) {
+		output.push(Value::Integer(i));
+		i = match i.checked_add(step) {
+			Some(next) => next,
+			None => break,
+		};
+	}
+
+	Ok(Value::List(output.into()))
+}
+
+// calls a function with the given arguments followed by the items of a list
+fn apply(args: &[Value], env: &mut Environment) -> Result<Value, Error> {
+	if args.len() < 2 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "apply requires at least two arguments".into(),
+		})
+	}
+
+	let (spread, _) = sequence_arg("apply", &args[args.len() - 1])?;
+	env.limits.step_by(spread.len() as u64)?;
+
+	let mut call_args = args[1..args.len() - 1].to_vec();
+	call_args.extend(spread.iter().cloned());
+
+	runtime::call(&args[0], call_args, env)
+}
+
+// calls a function with the items at each position of the given lists in turn,
+// up to the end of the shortest
+fn map(args: &[Value], env: &mut Environment) -> Result<Value, Error> {
+	if args.len() < 2 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "map requires a function and at least one list".into(),
+		})
+	}
+
+	let lists = args[1..]
+		.iter()
+		.map(|arg| sequence_arg("map", arg))
+		.collect::<Result<Vec<_>, _>>()?;
+	let length = lists.iter().map(|(list, _)| list.len()).min().unwrap_or(0);
+
+	let mut output = Vec::with_capacity(length);
+	for i in 0..length {
+		let call_args = lists.iter().map(|(list, _)| list[i].clone()).collect();
+		output.push(runtime::call(&args[0], call_args, env)?);
+	}
+
+	Ok(if lists[0].1 { Value::Vector(output) } else { Value::List(output.into()) })
+}
+
+fn filter(args: &[Value], env: &mut Environment) -> Result<Value, Error> {
+	if args.len() != 2 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "filter requires two arguments".into(),
+		})
+	}
+
+	let (list, is_vector) = sequence_arg("filter", &args[1])?;
+
+	let mut output = Vec::new();
+	for item in list.iter() {
+		if runtime::call(&args[0], vec![ item.clone() ], env)?.truthy() {
+			output.push(item.clone());
+		}
+	}
+
+	Ok(if is_vector { Value::Vector(output) } else { Value::List(output.into()) })
+}
+
+// combines the items of a list in turn with an accumulated value, starting
+// from the given initial value (or the first item), and ending early with any
+// value marked by reduced
+fn reduce(args: &[Value], env: &mut Environment) -> Result<Value, Error> {
+	let (list, initial) = match args {
+		[ _, list ] => (list, None),
+		[ _, initial, list ] => (list, Some(initial)),
+		_ => return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "reduce requires two or three arguments".into(),
+		}),
+	};
+
+	let (list, _) = sequence_arg("reduce", list)?;
+	let mut items = list.iter();
+
+	let mut accumulated = match initial.or_else(|| items.next()) {
+		Some(initial) => initial.clone(),
+		None => return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "reduce of empty list requires an initial value".into(),
+		}),
+	};
+
+	for item in items {
+		accumulated = match runtime::call(&args[0], vec![ accumulated, item.clone() ], env)? {
+			Value::Reduced(value) => return Ok(*value),
+			value => value,
+		};
+	}
+
+	Ok(accumulated)
+}
+
+fn reduced(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "reduced requires one argument".into(),
+		})
+	}
+
+	Ok(Value::Reduced(Box::new(args[0].clone())))
+}
+
+fn is_reduced(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "reduced? requires one argument".into(),
+		})
+	}
+
+	Ok(Value::Boolean(matches!(args[0], Value::Reduced(_))))
+}
+
+// orders the items of a list, naturally (as by lt) or by a function given two
+// items, which gives a number (negative, zero, or positive) or whether the first
+// is less than the second; items ordered equally keep their order
+fn sort(args: &[Value], env: &mut Environment) -> Result<Value, Error> {
+	if args.is_empty() || args.len() > 2 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "sort requires a list and optionally a function".into(),
+		})
+	}
+
+	let (list, is_vector) = sequence_arg("sort", &args[0])?;
+
+	let mut before = |a: &Value, b: &Value| -> Result<bool, Error> {
+		// each comparison counts as a step (as does each call of a function)
+		let comparator = match args.get(1) {
+			Some(comparator) => comparator,
+			None => return match env.limits.step().and_then(|_| compare("sort", a, b))? {
+				Some(ordering) => Ok(ordering == Ordering::Less),
+				None => Err(Error {
+					kind: ErrorKind::ArgumentError,
+					location: None,
+					source: None,
+					message: "sort cannot order nan".into(),
+				}),
+			},
+		};
+
+		match runtime::call(comparator, vec![ a.clone(), b.clone() ], env)? {
+			Value::Boolean(less) => Ok(less),
+			Value::Integer(integer) => Ok(integer < 0),
+			Value::Number(number) => Ok(number < 0.0),
+			other => Err(Error {
+				kind: ErrorKind::TypeError,
+				location: None,
+				source: None,
+				message: format!("sort expects its function to give a number or boolean, not {}", other.type_name()),
+			}),
+		}
+	};
+
+	let output = merge_sort(list.into_owned(), &mut before)?;
+	Ok(if is_vector { Value::Vector(output) } else { Value::List(output.into()) })
+}
+
+// a stable merge sort, which (unlike that of the standard library) stops at the
+// first error from comparing, and cannot be upset by an inconsistent ordering
+fn merge_sort(
+	mut items: Vec<Value>,
+	before: &mut dyn FnMut(&Value, &Value) -> Result<bool, Error>,
+) -> Result<Vec<Value>, Error> {
+	if items.len() < 2 {
+		return Ok(items)
+	}
+
+	let right = merge_sort(items.split_off(items.len() / 2), before)?;
+	let left = merge_sort(items, before)?;
+
+	let mut output = Vec::with_capacity(left.len() + right.len());
+	let mut left = left.into_iter().peekable();
+	let mut right = right.into_iter().peekable();
+	while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+		// an item on the right is taken first only when strictly before
+		if before(b, a)? {
+			output.extend(right.next());
+		} else {
+			output.extend(left.next());
+		}
+	}
+
+	output.extend(left);
+	output.extend(right);
+	Ok(output)
+}
+
+// the string documenting a function, or nil if it has none
+fn doc(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "doc requires one argument".into(),
+		})
+	}
+
+	match &args[0] {
+		Value::Function(function) => Ok(function.doc().map_or_else(Value::nil, |doc| Value::String(doc.into()))),
+		_ => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "doc expects a function".into(),
+		}),
+	}
+}
+
+// the form a call to a macro expands to, for seeing what a macro gives
+fn macroexpand(args: &[Value], env: &mut Environment) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "macroexpand requires one argument".into(),
+		})
+	}
+
+	expand::macroexpand(args[0].clone(), env)
+}
+
+// the form written in a string, as it would be read in a program
+fn read(args: &[Value], env: &mut Environment) -> Result<Value, Error> {
+	let src = match args {
+		[ Value::String(src) ] => src,
+		[ _ ] => return Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "read expects a string".into(),
+		}),
+		_ => return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "read requires one argument".into(),
+		}),
+	};
+
+	// an error is located within the string rather than the program, so its
+	// location is given as part of the message
+	let within_string = |error: Error| Error {
+		location: None,
+		message: match error.location {
+			Some(location) => format!("{}:{}: {}", location.start.line, location.start.column, error.message),
+			None => error.message,
+		},
+		..error
+	};
+
+	let tokens = lexer::tokenize(src).map_err(within_string)?;
+	let mut forms = parser::parse_with_readers(&mut tokens.into_iter(), &env.readers)
+		.map_err(within_string)?
+		.into_iter();
+
+	match (forms.next(), forms.len()) {
+		(Some(form), 0) => Ok(form),
+		(first, rest) => Err(Error {
+			kind: ErrorKind::SyntaxError,
+			location: None,
+			source: None,
+			message: format!("read expects one form (found {})", first.map_or(0, |_| rest + 1)),
+		}),
+	}
+}
+
+// evaluates a form as an expression of the program, among its variables and
+// definitions but not the locals of the caller
+fn eval(args: &[Value], env: &mut Environment) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "eval requires one argument".into(),
+		})
+	}
+
+	let root = Scope::root(&env.locals);
+	let locals = std::mem::replace(&mut env.locals, root);
+
+	// macros are expanded first, as in a program, unless the form holds values
+	// which cannot be written as source
+	let result = match Ast::from_value(&args[0], LocationArea::default()) {
+		Some(ast) => expand::expand(&ast, env)
+			.and_then(|ast| ast.to_value_with(&env.readers))
+			.and_then(|form| runtime::run(form, env)),
+		None => runtime::run(args[0].clone(), env),
+	};
+
+	env.locals = locals;
+	result
+}
+
+// the items of a list or vector, and whether it is a vector
+fn sequence_arg<'a>(name: &str, arg: &'a Value) -> Result<(Cow<'a, [Value]>, bool), Error> {
+	match arg {
+		Value::List(list) => Ok((list.iter().cloned().collect(), false)),
+		Value::Vector(vector) => Ok((Cow::Borrowed(vector), true)),
+		_ => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: format!("{} expects a list or vector", name),
+		}),
+	}
+}
+
+fn num(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "num requires one argument".into(),
+		})
+	}
+
+	use std::str::FromStr;
+	Ok(match &args[0] {
+		Value::Integer(integer) => Value::Integer(*integer),
+		Value::Number(number) => Value::Number(*number),
+		Value::String(string) => match i64::from_str(string) {
+			Ok(integer) => Value::Integer(integer),
+			Err(_) => match f64::from_str(string) {
+				Ok(number) => Value::Number(number),
+				Err(_) => Value::nil(),
+			},
+		},
+		Value::Boolean(boolean) => Value::Integer(*boolean as i64),
+		Value::List(list) => Value::Integer(list.len() as i64),
+		_ => return Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "num expects a number, string, boolean, or list".into(),
+		}),
+	})
+}
+
+fn is_infinite(args: &[Value]) -> Result<Value, Error> {
+	float_predicate("infinite?", args, f64::is_infinite)
+}
+
+fn is_nan(args: &[Value]) -> Result<Value, Error> {
+	float_predicate("nan?", args, f64::is_nan)
+}
+
+fn is_finite(args: &[Value]) -> Result<Value, Error> {
+	float_predicate("finite?", args, f64::is_finite)
+}
+
+fn float_predicate(
+	name: &str,
+	args: &[Value],
+	predicate: fn(f64) -> bool,
+) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: format!("{} requires one argument", name),
+		})
+	}
+
+	numbers(name, args)?;
+	Ok(Value::Boolean(predicate(float(&args[0]))))
+}
+
+fn keyword(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "keyword requires one argument".into(),
+		})
+	}
+
+	match &args[0] {
+		Value::Keyword(symbol) => Ok(Value::Keyword(symbol.clone())),
+		Value::String(string) => match Symbol::new(string.clone()) {
+			Some(symbol) => Ok(Value::Keyword(symbol)),
+			None => Err(Error {
+				kind: ErrorKind::ArgumentError,
+				location: None,
+				source: None,
+				message: format!("{:?} is not a valid keyword name", string),
+			}),
+		},
+		_ => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "keyword expects a string".into(),
+		}),
+	}
+}
+
+fn name(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "name requires one argument".into(),
+		})
+	}
+
+	match &args[0] {
+		Value::Keyword(symbol) | Value::Symbol(symbol) =>
+			Ok(Value::String(symbol.value().into())),
+		Value::String(string) => Ok(Value::String(string.clone())),
+		_ => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "name expects a keyword, symbol, or string".into(),
+		}),
+	}
+}
+
+fn char_to_code(args: &[Value]) -> Result<Value, Error> {
+	Ok(Value::Integer(char_arg("char->code", args)? as i64))
+}
+
+fn code_to_char(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "code->char requires one argument".into(),
+		})
+	}
+
+	match &args[0] {
+		Value::Integer(code) => u32::try_from(*code)
+			.ok()
+			.and_then(char::from_u32)
+			.map(Value::Char)
+			.ok_or_else(|| Error {
+				kind: ErrorKind::ArgumentError,
+				location: None,
+				source: None,
+				message: format!("{} is not a valid character", code),
+			}),
+		_ => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "code->char expects an integer".into(),
+		}),
+	}
+}
+
+fn char_upcase(args: &[Value]) -> Result<Value, Error> {
+	let ch = char_arg("char-upcase", args)?;
+
+	// characters without a single-character uppercase form are left as is
+	let mut upper = ch.to_uppercase();
+	Ok(Value::Char(match (upper.next(), upper.next()) {
+		(Some(upper), None) => upper,
+		_ => ch,
+	}))
+}
+
+fn char_downcase(args: &[Value]) -> Result<Value, Error> {
+	let ch = char_arg("char-downcase", args)?;
+
+	let mut lower = ch.to_lowercase();
+	Ok(Value::Char(match (lower.next(), lower.next()) {
+		(Some(lower), None) => lower,
+		_ => ch,
+	}))
+}
+
+fn char_arg(name: &str, args: &[Value]) -> Result<char, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: format!("{} requires one argument", name),
+		})
+	}
+
+	match &args[0] {
+		Value::Char(ch) => Ok(*ch),
+		_ => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: format!("{} expects a character", name),
+		}),
+	}
+}
+
+fn cat(args: &[Value]) -> Result<Value, Error> {
+	Ok(Value::String(cat_impl(args)))
+}
+
+// the display forms of the values, one after another
+fn cat_impl(args: &[Value]) -> String {
+	args.iter().map(Value::to_string).collect()
+}
+
+// the characters of a string from the start index up to (but not including)
+// the end index, or the end of the string if none is given
+fn substring(args: &[Value]) -> Result<Value, Error> {
+	if !(2..=3).contains(&args.len()) {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "substring requires 2-3 arguments".into(),
+		})
+	}
+
+	let (string, bounds) = match (&args[0], &args[1..]) {
+		(Value::String(string), [ Value::Integer(start) ]) => (string, (*start, None)),
+		(Value::String(string), [ Value::Integer(start), Value::Integer(end) ]) =>
+			(string, (*start, Some(*end))),
+		_ => return Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "substring expects a string and integer indices".into(),
+		}),
+	};
+
+	let length = string.chars().count() as i64;
+	let (start, end) = (bounds.0, bounds.1.unwrap_or(length));
+	if start < 0 || end > length || start > end {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: format!(
+				"substring indices {}..{} out of range for string of length {}",
+				start, end, length,
+			),
+		})
+	}
+
+	Ok(Value::String(
+		string.chars()
+			.skip(start as usize)
+			.take((end - start) as usize)
+			.collect()
+	))
+}
+
+// the parts of a string between each occurrence of a separator, or its
+// characters for an empty separator
+fn split(args: &[Value]) -> Result<Value, Error> {
+	let (string, separator) = match args {
+		[ Value::String(string), Value::String(separator) ] => (string, separator),
+		[ _, _ ] => return Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "split expects two strings".into(),
+		}),
+		_ => return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "split requires two arguments".into(),
+		}),
+	};
+
+	Ok(Value::List(if separator.is_empty() {
+		string.chars().map(|ch| Value::String(ch.to_string())).collect()
+	} else {
+		string.split(separator.as_str()).map(|part| Value::String(part.into())).collect()
+	}))
+}
+
+// the items of a list (as by cat) with a separator between each
+fn join(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 2 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "join requires two arguments".into(),
+		})
+	}
+
+	let separator = match &args[0] {
+		Value::String(separator) => separator,
+		_ => return Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "join expects a string separator".into(),
+		}),
+	};
+
+	let (items, _) = sequence_arg("join", &args[1])?;
+	Ok(Value::String(
+		items.iter()
+			.map(Value::to_string)
+			.collect::<Vec<_>>()
+			.join(separator)
+	))
+}
+
+fn upper(args: &[Value]) -> Result<Value, Error> {
+	Ok(Value::String(string_arg("upper", args)?.to_uppercase()))
+}
+
+fn lower(args: &[Value]) -> Result<Value, Error> {
+	Ok(Value::String(string_arg("lower", args)?.to_lowercase()))
+}
+
+fn string_arg<'a>(name: &str, args: &'a [Value]) -> Result<&'a str, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: format!("{} requires one argument", name),
+		})
+	}
+
+	match &args[0] {
+		Value::String(string) => Ok(string),
+		_ => Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: format!("{} expects a string", name),
+		}),
+	}
+}
+
+// raises an error carrying the value, which try can catch
+fn throw(args: &[Value]) -> Result<Value, Error> {
+	if args.len() != 1 {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "throw requires one argument".into(),
+		})
+	}
+
+	Err(Error {
+		kind: ErrorKind::UserError(Thrown(Rc::new(args[0].clone()))),
+		location: None,
+		source: None,
+		message: cat_impl(args),
+	})
+}
+
+fn print(args: &[Value]) -> Result<Value, Error> {
+	println!("{}", cat_impl(args));
+	Ok(Value::nil())
+}
+
+// prints the readable forms of the values, separated by spaces
+fn pr(args: &[Value]) -> Result<Value, Error> {
+	println!("{}", args.iter().map(Value::to_source).collect::<Vec<_>>().join(" "));
+	Ok(Value::nil())
+}
+
+fn input(args: &[Value]) -> Result<Value, Error> {
+	if !args.is_empty() {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "input takes no arguments".into(),
+		})
+	}
+
+	let mut buf = String::new();
+
+	if let Err(err) = std::io::stdin().read_line(&mut buf) {
+		return Err(Error {
+			kind: ErrorKind::IoError,
+			location: None,
+			source: None,
+			message: err.to_string(),
+		})
+	}
+
+	if buf.ends_with('\n') { buf.pop(); }
+	if buf.ends_with('\r') { buf.pop(); }
+
+	Ok(Value::String(buf))
+}
+
+// the contents of a file, as a string
+#[cfg(feature = "io")]
+fn slurp(args: &[Value]) -> Result<Value, Error> {
+	let path = match args {
+		[ Value::String(path) ] => path,
+		[ _ ] => return Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "slurp expects a string".into(),
+		}),
+		_ => return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "slurp requires one argument".into(),
+		}),
+	};
+
+	match std::fs::read_to_string(path) {
+		Ok(contents) => Ok(Value::String(contents)),
+		Err(err) => Err(Error {
+			kind: ErrorKind::IoError,
+			location: None,
+			source: None,
+			message: format!("cannot read {}: {}", path, err),
+		}),
+	}
+}
+
+// writes a file, replacing anything in it, with the display form of a value
+#[cfg(feature = "io")]
+fn spit(args: &[Value]) -> Result<Value, Error> {
+	let (path, contents) = match args {
+		[ Value::String(path), contents ] => (path, contents),
+		[ _, _ ] => return Err(Error {
+			kind: ErrorKind::TypeError,
+			location: None,
+			source: None,
+			message: "spit expects a string path".into(),
+		}),
+		_ => return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "spit requires two arguments".into(),
+		}),
+	};
+
+	match std::fs::write(path, contents.to_string()) {
+		Ok(()) => Ok(Value::nil()),
+		Err(err) => Err(Error {
+			kind: ErrorKind::IoError,
+			location: None,
+			source: None,
+			message: format!("cannot write {}: {}", path, err),
+		}),
+	}
+}
+
+// a line of standard input, as with input, but nil once the input has ended
+#[cfg(feature = "io")]
+fn read_line(args: &[Value]) -> Result<Value, Error> {
+	if !args.is_empty() {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "read-line takes no arguments".into(),
+		})
+	}
+
+	let mut buf = String::new();
+
+	match std::io::stdin().read_line(&mut buf) {
+		Ok(0) => return Ok(Value::nil()),
+		Ok(_) => (),
+		Err(err) => return Err(Error {
+			kind: ErrorKind::IoError,
+			location: None,
+			source: None,
+			message: err.to_string(),
+		}),
+	}
+
+	if buf.ends_with('\n') { buf.pop(); }
+	if buf.ends_with('\r') { buf.pop(); }
+
+	Ok(Value::String(buf))
+}
+
+fn time(args: &[Value]) -> Result<Value, Error> {
+	use std::time::{ SystemTime, UNIX_EPOCH };
+
+	if !args.is_empty() {
+		return Err(Error {
+			kind: ErrorKind::ArgumentError,
+			location: None,
+			source: None,
+			message: "time takes no arguments".into(),
+		})
+	}
+
+	Ok(
+		SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| Value::Number(d.as_secs_f64()))
+			.unwrap_or_default()
+	)
+}
+
+use std::sync::atomic::{ self, AtomicU64 };
+
+static RAND_SEED: AtomicU64 = AtomicU64::new(1);
+
 fn srand(args: &[Value]) -> Result<Value, Error> {
 	use std::collections::hash_map::DefaultHasher;
 	use std::hash::{ Hash, Hasher };
@@ -561,22 +1834,24 @@ fn srand(args: &[Value]) -> Result<Value, Error> {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
+			source: None,
 			message: "srand requires one argument".into(),
 		})
 	}
 
 	let mut hasher = DefaultHasher::new();
 	args[0].hash(&mut hasher);
-	RAND_SEED.store(hasher.finish(), Ordering::SeqCst);
+	RAND_SEED.store(hasher.finish(), atomic::Ordering::SeqCst);
 
 	Ok(Value::nil())
 }
 
 fn rand(args: &[Value]) -> Result<Value, Error> {
-	if args.len() > 0 {
+	if !args.is_empty() {
 		return Err(Error {
 			kind: ErrorKind::ArgumentError,
 			location: None,
+			source: None,
 			message: "rand takes no arguments".into(),
 		})
 	}
@@ -584,17 +1859,106 @@ fn rand(args: &[Value]) -> Result<Value, Error> {
 	let mut new_value = 0u64;
 
 	RAND_SEED.fetch_update(
-		Ordering::SeqCst,
-		Ordering::SeqCst,
+		atomic::Ordering::SeqCst,
+		atomic::Ordering::SeqCst,
 		|seed| {
 			// values from glibc posix
 			const MULTIPLIER: u64 = 25214903917;
 			const INCREMENT: u64 = 11;
 
-			new_value = seed.overflowing_mul(MULTIPLIER).0.overflowing_add(11).0;
+			new_value = seed.overflowing_mul(MULTIPLIER).0.overflowing_add(INCREMENT).0;
 			Some(new_value)
 		},
 	).unwrap();
 
-	Ok(Value::Number((new_value & u32::MAX as u64) as f32))
+	Ok(Value::Integer((new_value & u32::MAX as u64) as i64))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{ eval, Value, ErrorKind };
+
+	fn run(src: &str) -> Result<Value, ErrorKind> {
+		eval(src, None).map_err(|error| error.kind)
+	}
+
+	#[test]
+	fn reduce() {
+		assert_eq!(run("(reduce + [1 2 3])"), Ok(Value::Integer(6)));
+		assert_eq!(run("(reduce + 10 [1 2 3])"), Ok(Value::Integer(16)));
+
+		// an empty list has no first item to begin from
+		assert_eq!(run("(reduce + [])"), Err(ErrorKind::ArgumentError));
+		assert_eq!(run("(reduce + 0 [])"), Ok(Value::Integer(0)));
+
+		// reduced ends early, so that the rest are never reached
+		let found = "(reduce (fun (a b) (if (gt b 3) (reduced b) (add a b))) [1 2 3 4 \"five\"])";
+		assert_eq!(run(found), Ok(Value::Integer(4)));
+	}
+
+	#[test]
+	fn comparisons() {
+		let holds = |src| run(src) == Ok(Value::Boolean(true));
+
+		// integers and numbers are equal by value, though nan is equal to nothing
+		assert!(holds("(= 1 1.0)"));
+		assert!(holds("(= [1 '(2)] [1.0 '(2.0)])"));
+		assert!(!holds("(= 1 1.5)"));
+		assert!(!holds("(= nan nan)"));
+
+		// not= holds unless all are equal, whereas ne needs every one distinct
+		assert!(holds("(not= 1 2)"));
+		assert!(!holds("(not= 1 1.0)"));
+		assert!(holds("(not= 1 2 2)"));
+		assert!(!holds("(ne 1 2 2)"));
+
+		// each argument is compared with the next
+		assert!(holds("(< 1 2 3)"));
+		assert!(!holds("(< 1 3 2)"));
+		assert!(holds("(<= 1 1.0 2)"));
+		assert!(holds("(> \"b\" \"a\")"));
+		assert!(!holds("(< 1 nan)"));
+
+		assert_eq!(run("(< 1 \"a\")"), Err(ErrorKind::TypeError));
+		assert_eq!(run("(< 3 2 \"a\")"), Err(ErrorKind::TypeError));
+		assert_eq!(run("(< 1)"), Err(ErrorKind::ArgumentError));
+	}
+
+	#[test]
+	fn sets() {
+		let shown = |src| eval(src, None).unwrap().to_string();
+		assert_eq!(shown("(set 3 1 2 1 3)"), "#{3 1 2}");
+		assert_eq!(shown("(eq (set 1 2) (set 2 1))"), "true");
+		assert_eq!(shown("(union (set 1 2) (set 2 3))"), "#{1 2 3}");
+		assert_eq!(shown("(intersection (set 1 2 3) (set 3 2 9))"), "#{2 3}");
+		assert_eq!(shown("(difference (set 1 2 3) (set 2))"), "#{1 3}");
+		assert_eq!(shown("(remove (add (set 1) 2 3) 1)"), "#{2 3}");
+		assert_eq!(shown("(contains? (set (set 1 2)) (set 2 1))"), "true");
+
+		let error = eval("(set 1 [2 (fun () 3)])", None).unwrap_err();
+		assert_eq!(error.kind, ErrorKind::TypeError);
+		assert!(error.message.starts_with("function cannot be in a set"));
+	}
+
+	#[cfg(feature = "io")]
+	#[test]
+	fn files() {
+		use crate::Symbol;
+		use crate::runtime::Environment;
+
+		let path = std::env::temp_dir().join(format!("facsimile-test-{}.txt", std::process::id()));
+		let mut env = Environment {
+			symbols: maplit::hashmap! {
+				Symbol::new("path".into()).unwrap() => Value::String(path.display().to_string()),
+			},
+			..Default::default()
+		};
+
+		let contents = eval(r#"(spit path "one\ntwo") (slurp path)"#, Some(&mut env)).unwrap();
+		assert_eq!(contents, Value::String("one\ntwo".into()));
+
+		std::fs::remove_file(&path).unwrap();
+		let error = eval("(slurp path)", Some(&mut env)).unwrap_err();
+		assert_eq!(error.kind, ErrorKind::IoError);
+	}
 }
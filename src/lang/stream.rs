@@ -0,0 +1,115 @@
+use super::{ Error, ErrorKind, LocationArea };
+use super::lexer::{ Token, TokenKind };
+
+// buffered tokens, allowing any amount of lookahead and backtracking
+pub struct TokenStream<'src> {
+	tokens: Vec<Token<'src>>,
+	position: usize,
+}
+
+// a position in a stream which can be returned to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Checkpoint(usize);
+
+impl<'src> TokenStream<'src> {
+	pub fn new(tokens: impl IntoIterator<Item = Token<'src>>) -> Self {
+		Self {
+			tokens: tokens.into_iter().collect(),
+			position: 0,
+		}
+	}
+
+	pub fn peek(&self) -> Option<&Token<'src>> {
+		self.peek_n(0)
+	}
+
+	// the token n places ahead of the next one (so 0 is the next token)
+	pub fn peek_n(&self, n: usize) -> Option<&Token<'src>> {
+		self.tokens.get(self.position + n)
+	}
+
+	// takes the next token, failing unless it is of the given kind
+	pub fn expect(&mut self, kind: &TokenKind) -> Result<Token<'src>, Error> {
+		match self.peek() {
+			Some(token) if token.kind == *kind => Ok(self.next().unwrap()),
+			Some(token) => Err(Error {
+				kind: ErrorKind::SyntaxError,
+				location: Some(token.location),
+				source: None,
+				message: format!("expected {}, found {}", kind, token.kind),
+			}),
+			None => Err(Error {
+				kind: ErrorKind::IncompleteError,
+				location: self.end(),
+				source: None,
+				message: format!("expected {}, found end of input", kind),
+			}),
+		}
+	}
+
+	pub fn checkpoint(&self) -> Checkpoint {
+		Checkpoint(self.position)
+	}
+
+	pub fn rollback(&mut self, checkpoint: Checkpoint) {
+		self.position = checkpoint.0;
+	}
+
+	// the end of the last token, if there were any
+	fn end(&self) -> Option<LocationArea> {
+		self.tokens.last().map(|token| token.location.end.into())
+	}
+}
+
+impl<'src> Iterator for TokenStream<'src> {
+	type Item = Token<'src>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let token = self.tokens.get(self.position)?.clone();
+		self.position += 1;
+
+		Some(token)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::TokenStream;
+	use crate::ErrorKind;
+	use crate::lexer::{ tokenize, TokenKind };
+
+	#[test]
+	fn lookahead() {
+		let mut stream = TokenStream::new(tokenize("(a 1)").unwrap());
+		assert_eq!(stream.peek().map(|token| &token.kind), Some(&TokenKind::LeftParen));
+		assert_eq!(stream.peek_n(2).map(|token| &token.kind), Some(&TokenKind::Integer(1)));
+
+		// peeking takes nothing
+		assert!(stream.expect(&TokenKind::LeftParen).is_ok());
+		let error = stream.expect(&TokenKind::RightParen).unwrap_err();
+		assert_eq!(error.kind, ErrorKind::SyntaxError);
+		assert_eq!(error.location.map(|location| location.start.column), Some(2));
+	}
+
+	#[test]
+	fn rollback() {
+		let mut stream = TokenStream::new(tokenize("a b").unwrap());
+		let checkpoint = stream.checkpoint();
+		stream.next();
+		stream.next();
+
+		stream.rollback(checkpoint);
+		assert_eq!(stream.next().map(|token| token.kind), Some(TokenKind::Symbol("a".into())));
+	}
+
+	#[test]
+	fn end() {
+		let mut stream = TokenStream::new(tokenize("(").unwrap());
+		stream.next();
+
+		// reported at the end of the last token
+		let error = stream.expect(&TokenKind::RightParen).unwrap_err();
+		assert_eq!(error.kind, ErrorKind::IncompleteError);
+		assert_eq!(error.location.map(|location| location.start.column), Some(1));
+	}
+}
@@ -0,0 +1,219 @@
+use super::Symbol;
+use super::ast::{ Ast, AstKind };
+
+// walks a tree; each method by default visits the expressions within its node,
+// so that an implementation need only override those for the nodes it handles
+// (calling walk to continue into their children)
+pub trait Visitor {
+	fn visit(&mut self, ast: &Ast) {
+		match &ast.kind {
+			AstKind::Program(items) => self.visit_program(ast, items),
+			AstKind::List(items) => match definition(items) {
+				Some((name, args, body)) => self.visit_definition(ast, name, args, body),
+				None => self.visit_call(ast, items),
+			},
+			AstKind::Vector(items) => self.visit_vector(ast, items),
+			AstKind::Map(items) => self.visit_map(ast, items),
+			AstKind::Symbol(symbol) => self.visit_symbol(ast, symbol),
+			_ => self.visit_literal(ast),
+		}
+	}
+
+	fn visit_program(&mut self, _ast: &Ast, items: &[Ast]) {
+		walk(self, items);
+	}
+
+	// any list other than a definition, including special forms
+	fn visit_call(&mut self, _ast: &Ast, items: &[Ast]) {
+		walk(self, items);
+	}
+
+	// a def or defmacro, whose name and arguments are not expressions, and so are
+	// not visited by default
+	fn visit_definition(&mut self, _ast: &Ast, _name: &Ast, _args: &Ast, body: &[Ast]) {
+		walk(self, body);
+	}
+
+	fn visit_vector(&mut self, _ast: &Ast, items: &[Ast]) {
+		walk(self, items);
+	}
+
+	// keys and values alternating
+	fn visit_map(&mut self, _ast: &Ast, items: &[Ast]) {
+		walk(self, items);
+	}
+
+	fn visit_symbol(&mut self, _ast: &Ast, _symbol: &Symbol) {}
+
+	// a keyword, number, char, string, boolean, or nil
+	fn visit_literal(&mut self, _ast: &Ast) {}
+}
+
+pub fn walk<V: Visitor + ?Sized>(visitor: &mut V, items: &[Ast]) {
+	for item in items {
+		visitor.visit(item);
+	}
+}
+
+// rebuilds a tree; each method by default rebuilds its node from its
+// transformed expressions, so that an implementation need only override those
+// for the nodes it changes (calling rebuild to transform their children)
+pub trait Transform {
+	fn transform(&mut self, ast: Ast) -> Ast {
+		match &ast.kind {
+			AstKind::Program(_) => self.transform_program(ast),
+			AstKind::List(items) if definition(items).is_some() =>
+				self.transform_definition(ast),
+			AstKind::List(_) => self.transform_call(ast),
+			AstKind::Vector(_) => self.transform_vector(ast),
+			AstKind::Map(_) => self.transform_map(ast),
+			AstKind::Symbol(_) => self.transform_symbol(ast),
+			_ => self.transform_literal(ast),
+		}
+	}
+
+	fn transform_program(&mut self, ast: Ast) -> Ast {
+		rebuild(self, ast)
+	}
+
+	fn transform_call(&mut self, ast: Ast) -> Ast {
+		rebuild(self, ast)
+	}
+
+	// only the body of a definition is transformed by default
+	fn transform_definition(&mut self, ast: Ast) -> Ast {
+		let items = match &ast.kind {
+			AstKind::List(items) => items,
+			_ => return ast,
+		};
+
+		let items = items[..3]
+			.iter()
+			.cloned()
+			.chain(items[3..].iter().map(|item| self.transform(item.clone())))
+			.collect();
+
+		Ast { kind: AstKind::List(items), location: ast.location }
+	}
+
+	fn transform_vector(&mut self, ast: Ast) -> Ast {
+		rebuild(self, ast)
+	}
+
+	fn transform_map(&mut self, ast: Ast) -> Ast {
+		rebuild(self, ast)
+	}
+
+	fn transform_symbol(&mut self, ast: Ast) -> Ast {
+		ast
+	}
+
+	fn transform_literal(&mut self, ast: Ast) -> Ast {
+		ast
+	}
+}
+
+// the node with each of its expressions transformed, if it has any
+pub fn rebuild<T: Transform + ?Sized>(transform: &mut T, ast: Ast) -> Ast {
+	let items = match &ast.kind {
+		AstKind::Program(items) |
+		AstKind::List(items) |
+		AstKind::Vector(items) |
+		AstKind::Map(items) => items
+			.iter()
+			.map(|item| transform.transform(item.clone()))
+			.collect(),
+		_ => return ast,
+	};
+
+	let kind = match ast.kind {
+		AstKind::Program(_) => AstKind::Program(items),
+		AstKind::List(_) => AstKind::List(items),
+		AstKind::Vector(_) => AstKind::Vector(items),
+		AstKind::Map(_) => AstKind::Map(items),
+		_ => unreachable!(),
+	};
+
+	Ast { kind, location: ast.location }
+}
+
+// the name, arguments, and body of a def or defmacro
+fn definition(items: &[Ast]) -> Option<(&Ast, &Ast, &[Ast])> {
+	match items {
+		[ Ast { kind: AstKind::Symbol(symbol), .. }, name, args, body @ .. ]
+			if matches!(symbol.value(), "def" | "defmacro") => Some((name, args, body)),
+		_ => None,
+	}
+}
+
+// removes the branches of an if which can never be taken, as their condition
+// is a literal which is never truthy (or follows one which always is)
+pub struct DeadBranches;
+
+impl Transform for DeadBranches {
+	fn transform_call(&mut self, ast: Ast) -> Ast {
+		let ast = rebuild(self, ast);
+		let items = match &ast.kind {
+			AstKind::List(items) => items,
+			_ => unreachable!(),
+		};
+
+		match items.first().map(|head| &head.kind) {
+			Some(AstKind::Symbol(symbol)) if symbol.value() == "if" && items.len() >= 3 => (),
+			_ => return ast,
+		}
+
+		let mut branches = Vec::new();
+		let mut otherwise = None;
+		for pair in items[1..].chunks(2) {
+			let (condition, branch) = match pair {
+				[ condition, branch ] => (condition, branch),
+				[ last ] => {
+					otherwise = Some(last.clone());
+					break
+				},
+				_ => unreachable!(),
+			};
+
+			match literal_truth(condition) {
+				// a condition which always holds ends the if
+				Some(true) => {
+					otherwise = Some(branch.clone());
+					break
+				},
+				Some(false) => (),
+				None => branches.push((condition.clone(), branch.clone())),
+			}
+		}
+
+		// if no conditions remain, only the branch taken otherwise is left (which
+		// is nil when there is none)
+		if branches.is_empty() {
+			return otherwise.unwrap_or(Ast { kind: AstKind::Nil, location: ast.location })
+		}
+
+		let mut pruned = vec![ items[0].clone() ];
+		for (condition, branch) in branches {
+			pruned.push(condition);
+			pruned.push(branch);
+		}
+		pruned.extend(otherwise);
+
+		Ast { kind: AstKind::List(pruned.into()), location: ast.location }
+	}
+}
+
+// whether an expression is always or never truthy, if it is a literal
+fn literal_truth(ast: &Ast) -> Option<bool> {
+	match &ast.kind {
+		AstKind::List(items) if items.is_empty() => Some(false),
+		AstKind::Keyword(_) |
+		AstKind::Integer(_) |
+		AstKind::Number(_) |
+		AstKind::Char(_) |
+		AstKind::String(_) |
+		AstKind::Boolean(_) |
+		AstKind::Nil => ast.to_value().ok().map(|value| value.truthy()),
+		_ => None,
+	}
+}
@@ -1,41 +1,235 @@
-mod lang;
+// errors are larger than clippy would like, as in the library
+#![allow(clippy::result_large_err)]
 
-use lang::{ Value, Symbol };
-use lang::runtime::Environment;
+use facsimile::{ Value, Symbol };
+use facsimile::runtime::Environment;
 
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{ Read, Write };
+use std::path::Path;
 
 fn main() {
 	let mut args = std::env::args();
 
 	let input_file = match args.nth(1) {
+		Some(arg) if arg == "check" => return check(args.next()),
+		Some(arg) if arg == "fmt" => return fmt(args),
+		Some(arg) if arg == "ast" => return ast(args.next()),
 		Some(arg) => arg,
-		None => unimplemented!("REPL"),
+		None => return repl(),
 	};
 
-	let mut input = BufReader::new(match File::open(&input_file) {
-		Ok(file) => file,
-		Err(error) => {
-			eprintln!("Error: {}", error);
-			std::process::exit(1);
-		},
-	});
+	// a path of - reads the script from standard input
+	let source = if input_file == "-" {
+		"<stdin>".to_string()
+	} else {
+		input_file.clone()
+	};
+
+	let mut input: Box<dyn Read> = if input_file == "-" {
+		Box::new(std::io::stdin())
+	} else {
+		Box::new(match File::open(&input_file) {
+			Ok(file) => file,
+			Err(error) => {
+				eprintln!("Error: {}", error);
+				std::process::exit(1);
+			},
+		})
+	};
 
 	let mut prog_args = args
-		.map(|arg| Value::String(arg))
+		.map(Value::String)
 		.collect::<Vec<_>>();
 	prog_args.insert(0, Value::String(input_file));
 
 	let mut env = Environment {
 		symbols: maplit::hashmap! {
-			Symbol::new("args".into()).unwrap() => Value::List(prog_args),
+			Symbol::new("args".into()).unwrap() => Value::List(prog_args.into()),
 		},
+		// imports are found relative to the script, or the working directory when
+		// it is read from standard input
+		file: (source != "<stdin>").then(|| Path::new(&source).into()),
 		..Default::default()
 	};
 
-	if let Err(error) = lang::eval_read(&mut input, Some(&mut env)) {
-		eprintln!("{:?}: {}", error.kind, error.message);
+	if let Err(error) = facsimile::eval_read(&mut input, Some(&mut env)) {
+		eprintln!("{}", error.with_source(source.as_str()));
+		std::process::exit(1);
+	}
+}
+
+// reports every syntax error in a source, without evaluating it
+fn check(input_file: Option<String>) {
+	let (source, src) = read_source("check", input_file);
+
+	let (_, errors) = facsimile::parse_all(&src);
+	for error in &errors {
+		eprintln!("{}", error.clone().with_source(source.as_str()));
+	}
+
+	if !errors.is_empty() {
+		std::process::exit(1);
+	}
+}
+
+// prints the syntax tree of a source as json, for other tools
+fn ast(input_file: Option<String>) {
+	let (source, src) = read_source("ast", input_file);
+
+	match facsimile::parse(&src) {
+		Ok(ast) => println!("{}", ast.to_json()),
+		Err(errors) => {
+			for error in errors {
+				eprintln!("{}", error.with_source(source.as_str()));
+			}
+
+			std::process::exit(1);
+		},
+	}
+}
+
+// prints a source in canonical form, or with --write replaces the file with it,
+// or with --check fails if it is not already in canonical form
+fn fmt(args: impl Iterator<Item = String>) {
+	let (mut write, mut check) = (false, false);
+	let mut input_file = None;
+	for arg in args {
+		match arg.as_str() {
+			"--write" | "-w" => write = true,
+			"--check" => check = true,
+			_ if input_file.is_none() => input_file = Some(arg),
+			_ => {
+				eprintln!("Error: unexpected argument {:?}", arg);
+				std::process::exit(1);
+			},
+		}
+	}
+
+	if write && input_file.as_deref() == Some("-") {
+		eprintln!("Error: cannot write formatted source to standard input");
 		std::process::exit(1);
 	}
+
+	let (source, src) = read_source("fmt", input_file);
+
+	// a source with errors is left as it is
+	let formatted = match facsimile::format(&src) {
+		Ok(formatted) => formatted,
+		Err(errors) => {
+			for error in errors {
+				eprintln!("{}", error.with_source(source.as_str()));
+			}
+
+			std::process::exit(1);
+		},
+	};
+
+	if check {
+		if formatted != src {
+			eprintln!("{}: not formatted", source);
+			std::process::exit(1);
+		}
+	} else if write {
+		if formatted != src {
+			if let Err(error) = std::fs::write(&source, formatted) {
+				eprintln!("Error: {}", error);
+				std::process::exit(1);
+			}
+		}
+	} else {
+		print!("{}", formatted);
+	}
+}
+
+// reads the whole of a source file (or standard input, given -), along with
+// the name to report it by
+fn read_source(command: &str, input_file: Option<String>) -> (String, String) {
+	let input_file = match input_file {
+		Some(input_file) => input_file,
+		None => {
+			eprintln!("Error: {} requires a source file", command);
+			std::process::exit(1);
+		},
+	};
+
+	let (source, read) = if input_file == "-" {
+		let mut src = String::new();
+		("<stdin>".to_string(), std::io::stdin().read_to_string(&mut src).map(|_| src))
+	} else {
+		let read = std::fs::read_to_string(&input_file);
+		(input_file, read)
+	};
+
+	match read {
+		Ok(src) => (source, src),
+		Err(error) => {
+			eprintln!("Error: {}", error);
+			std::process::exit(1);
+		},
+	}
+}
+
+fn repl() {
+	let mut env = Environment {
+		symbols: maplit::hashmap! {
+			Symbol::new("args".into()).unwrap() => Value::nil(),
+		},
+		..Default::default()
+	};
+
+	// lines are collected until they form complete expressions
+	let mut buffer = String::new();
+	loop {
+		print!("{}", if buffer.is_empty() { "> " } else { ". " });
+		std::io::stdout().flush().unwrap();
+
+		match std::io::stdin().read_line(&mut buffer) {
+			Ok(0) => break println!(),
+			Ok(_) => (),
+			Err(error) => {
+				eprintln!("Error: {}", error);
+				std::process::exit(1);
+			},
+		}
+
+		// :help name shows how to call a function and what it does
+		let mut words = buffer.split_whitespace();
+		if words.next() == Some(":help") {
+			match (words.next(), words.next()) {
+				(Some(name), None) => help(name, &mut env),
+				_ => eprintln!("Error: :help requires the name of a function"),
+			}
+
+			buffer.clear();
+			continue
+		}
+
+		let result = facsimile::parser::parse_incremental(&buffer)
+			.and_then(|parsed| match parsed {
+				Some(_) => facsimile::eval(&buffer, Some(&mut env)).map(Some),
+				None => Ok(None),
+			});
+
+		match result {
+			Ok(None) => continue,
+			// results are shown in readable form, as they would be written
+			Ok(Some(value)) => println!("{}", value.to_source()),
+			Err(error) => eprintln!("{}", error.with_source("<repl>")),
+		}
+
+		buffer.clear();
+	}
+}
+
+// prints the parameters of a function and the string documenting it
+fn help(name: &str, env: &mut Environment) {
+	let function = match facsimile::eval(name, Some(env)) {
+		Ok(Value::Function(function)) => function,
+		Ok(other) => return eprintln!("Error: {} is {}, not a function", name, other.type_name()),
+		Err(error) => return eprintln!("{}", error.with_source("<repl>")),
+	};
+
+	println!("{}", function);
+	println!("{}", function.doc().unwrap_or("(no documentation)"));
 }